@@ -1,4 +1,3 @@
-use anyhow::bail;
 use clap::{Subcommand, ValueEnum};
 
 use crate::reporter::CliReporter;
@@ -19,7 +18,8 @@ pub enum Commands {
     /// Sfx archives related functions
     Sfx(sfx::SfxCommands),
     /// Stream archives related functions
-    Stream,
+    #[command(subcommand)]
+    Stream(stream::StreamCommands),
     /// Other useful utilities
     #[command(subcommand, alias = "utility")]
     Utilities(utilities::UtilitiesCommands),
@@ -30,7 +30,7 @@ impl Commands {
         match self {
             Self::Config(c) => c.command(reporter),
             Self::Sfx(c) => c.command(reporter),
-            Self::Stream => bail!("Not yet implmented"),
+            Self::Stream(c) => c.command(reporter),
             Self::Utilities(c) => c.command(reporter),
         }
     }
@@ -1,9 +1,21 @@
-use std::{ffi::OsStr, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
 use clap::{Parser, Subcommand, ValueEnum, ValueHint};
 
-use saamt_core::{reporter::Logger, sfx_prelude::*};
+use saamt_core::{
+    platform::Platform,
+    reporter::Logger,
+    sfx::{ImportOptions, SfxArchive},
+    sfx_prelude::*,
+};
+#[cfg(feature = "wav")]
+use saamt_core::{sfx::Resample, utils::wav::Downmix};
 
+#[cfg(all(feature = "pc", feature = "ps2"))]
+use crate::commands::stream::PlatformArg;
 use crate::{commands::utils, reporter::CliReporter};
 
 #[derive(Debug, Parser)]
@@ -37,6 +49,52 @@ pub enum Action {
         #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
         files_folder: PathBuf,
         output_file: Option<PathBuf>,
+        /// Where to save the updated lookup table, by default it will be saved next to
+        /// the original one with `.new.dat` extension, but only if the offset or size
+        /// of any bank changed
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        output_lookup: Option<PathBuf>,
+        /// Path to BankSlot.dat, if given the slot buffer sizes get updated to fit the new banks
+        #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+        bank_slot: Option<PathBuf>,
+        /// Where to save the updated BankSlot.dat, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath, requires = "bank_slot")]
+        output_bank_slot: Option<PathBuf>,
+        /// Resample the wav files before importing them, either to the sample rate of
+        /// the original sound (`original`) or to the given sample rate
+        #[cfg(feature = "wav")]
+        #[arg(long, value_name = "original|RATE", value_parser = parse_resample)]
+        resample: Option<Resample>,
+        /// How the channels of the wav files that aren't mono get mixed down to mono
+        #[cfg(feature = "wav")]
+        #[arg(long, value_enum, default_value_t = DownmixArg::Average)]
+        downmix: DownmixArg,
+        /// Add TPDF dither when converting 24/32-bit and float wav files to 16-bit
+        #[cfg(feature = "wav")]
+        #[arg(long)]
+        dither: bool,
+    },
+    /// Convert all the sounds of the sfx archive to the other platform (PC to PS2 or PS2 to PC)
+    #[cfg(all(feature = "pc", feature = "ps2"))]
+    #[command(arg_required_else_help = true)]
+    Convert {
+        /// Target platform, the sfx archive should be from the other platform
+        #[arg(long, value_enum)]
+        to: PlatformArg,
+        output_file: Option<PathBuf>,
+        /// Where to save the updated lookup table, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        output_lookup: Option<PathBuf>,
+        /// Path to BankSlot.dat of the target platform, if given the slot buffer sizes
+        /// get updated to fit the converted banks
+        #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+        bank_slot: Option<PathBuf>,
+        /// Where to save the updated BankSlot.dat, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath, requires = "bank_slot")]
+        output_bank_slot: Option<PathBuf>,
     },
 }
 
@@ -45,6 +103,8 @@ impl Action {
         match self {
             Action::Export { .. } => "Export",
             Action::Import { .. } => "Import",
+            #[cfg(all(feature = "pc", feature = "ps2"))]
+            Action::Convert { .. } => "Convert",
         }
     }
 }
@@ -56,6 +116,9 @@ pub enum Type {
     Banks,
     /// Export/Import raw sound data
     RawSound,
+    /// Export/Import as Wav of the detected platform (PC or PS2)
+    #[cfg(all(feature = "pc", feature = "ps2"))]
+    Wav,
     /// Export/Import as PC Wav
     #[cfg(feature = "pc")]
     PcWav,
@@ -67,10 +130,35 @@ pub enum Type {
     Ps2Wav,
 }
 
+#[cfg(feature = "wav")]
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DownmixArg {
+    /// Average of all the channels
+    Average,
+    /// Only keep the left channel
+    Left,
+    /// Only keep the right channel
+    Right,
+    /// Equal power sum of the channels, turned down if it clip
+    Mid,
+}
+
+#[cfg(feature = "wav")]
+impl From<DownmixArg> for Downmix {
+    fn from(value: DownmixArg) -> Self {
+        match value {
+            DownmixArg::Average => Downmix::Average,
+            DownmixArg::Left => Downmix::Left,
+            DownmixArg::Right => Downmix::Right,
+            DownmixArg::Mid => Downmix::Mid,
+        }
+    }
+}
+
 impl SfxCommands {
     pub fn command(self, mut reporter: CliReporter) -> anyhow::Result<()> {
-        let mut sfx = SfxManager::new(self.lookup_table, self.pak_names, &mut reporter)?;
-        let archive = sfx.load(&self.input_archive, &mut reporter)?;
+        let mut sfx = SfxManager::new(&self.lookup_table, self.pak_names.as_ref(), &mut reporter)?;
+        let mut archive = sfx.load(&self.input_archive, &mut reporter)?;
 
         reporter.info(format!("SFX action: {}", self.action.name()));
 
@@ -88,7 +176,7 @@ impl SfxCommands {
                             .export_all_banks(output_dir, &mut reporter)?;
                     }
                     dtype => {
-                        let sound_type = get_sound_type(dtype);
+                        let sound_type = get_sound_type(dtype, &mut archive, &mut reporter)?;
 
                         archive
                             .banks()
@@ -101,19 +189,31 @@ impl SfxCommands {
             Action::Import {
                 files_folder,
                 output_file,
+                output_lookup,
+                bank_slot,
+                output_bank_slot,
+                #[cfg(feature = "wav")]
+                resample,
+                #[cfg(feature = "wav")]
+                downmix,
+                #[cfg(feature = "wav")]
+                dither,
             } => {
-                let output_file = output_file.unwrap_or_else(|| {
-                    let extension = self
-                        .input_archive
-                        .extension()
-                        .and_then(OsStr::to_str)
-                        .map(ToOwned::to_owned)
-                        .unwrap_or_default();
-                    self.input_archive.with_extension(extension + ".new")
-                });
+                let output_file =
+                    output_file.unwrap_or_else(|| default_output(&self.input_archive));
 
                 reporter.info(format!("Import type: {:?}", self.dtype));
 
+                archive.set_import_options(ImportOptions {
+                    #[cfg(feature = "wav")]
+                    resample: resample.unwrap_or_default(),
+                    #[cfg(feature = "wav")]
+                    downmix: downmix.into(),
+                    #[cfg(feature = "wav")]
+                    dither,
+                });
+
+                let original_lookup = sfx.lookup_table.clone();
                 match self.dtype {
                     Type::Banks => {
                         archive.import_banks(
@@ -124,7 +224,7 @@ impl SfxCommands {
                         )?;
                     }
                     dtype => {
-                        let sound_type = get_sound_type(dtype);
+                        let sound_type = get_sound_type(dtype, &mut archive, &mut reporter)?;
 
                         archive.import_sounds(
                             sound_type,
@@ -136,16 +236,122 @@ impl SfxCommands {
                     }
                 }
 
+                save_configs(
+                    &sfx,
+                    &self.lookup_table,
+                    output_lookup,
+                    sfx.lookup_table != original_lookup,
+                    bank_slot,
+                    output_bank_slot,
+                    &mut reporter,
+                )?;
+
                 reporter.good("Import finished.");
             }
+            #[cfg(all(feature = "pc", feature = "ps2"))]
+            Action::Convert {
+                to,
+                output_file,
+                output_lookup,
+                bank_slot,
+                output_bank_slot,
+            } => {
+                let output_file =
+                    output_file.unwrap_or_else(|| default_output(&self.input_archive));
+
+                archive.convert(to.into(), output_file, &mut sfx.lookup_table, &mut reporter)?;
+
+                save_configs(
+                    &sfx,
+                    &self.lookup_table,
+                    output_lookup,
+                    true,
+                    bank_slot,
+                    output_bank_slot,
+                    &mut reporter,
+                )?;
+
+                reporter.good("Convert finished.");
+            }
         }
         Ok(())
     }
 }
 
-fn get_sound_type(dtype: Type) -> SoundType {
-    match dtype {
+/// Default path of the new sfx archive, next to the input one with `.new` extension.
+fn default_output(input_archive: &Path) -> PathBuf {
+    let extension = input_archive
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(ToOwned::to_owned)
+        .unwrap_or_default();
+    input_archive.with_extension(extension + ".new")
+}
+
+/// Save the updated lookup table if the layout of the banks changed or `output_lookup`
+/// was given, and update the BankSlot.dat if it was given.
+fn save_configs(
+    sfx: &SfxManager,
+    lookup_table: &Path,
+    output_lookup: Option<PathBuf>,
+    lookup_changed: bool,
+    bank_slot: Option<PathBuf>,
+    output_bank_slot: Option<PathBuf>,
+    reporter: &mut CliReporter,
+) -> anyhow::Result<()> {
+    if lookup_changed || output_lookup.is_some() {
+        reporter.info("Saving the updated lookup table.");
+        let output_lookup = output_lookup.unwrap_or_else(|| lookup_table.with_extension("new.dat"));
+        sfx.update_lookup(Some(output_lookup))?;
+        reporter.good("Lookup table saved.");
+    } else {
+        reporter.info("The banks kept their offsets and sizes, the lookup table is unchanged.");
+    }
+
+    if let Some(bank_slot) = bank_slot {
+        let output_bank_slot =
+            output_bank_slot.unwrap_or_else(|| bank_slot.with_extension("new.dat"));
+        sfx.update_bank_slot(&bank_slot, Some(output_bank_slot), reporter)?;
+        reporter.good("BankSlot saved.");
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "wav")]
+fn parse_resample(value: &str) -> Result<Resample, String> {
+    if value.eq_ignore_ascii_case("original") {
+        return Ok(Resample::Original);
+    }
+
+    match value.parse::<u16>() {
+        Ok(rate) if rate != 0 => Ok(Resample::Rate(rate)),
+        _ => Err("You need to pass `original` or a sample rate between 1 and 65535.".to_owned()),
+    }
+}
+
+/// Get the [`SoundType`] for the `dtype`, the platform of the archive get detected for
+/// picking the wav type and a warning get logged if the type is for another platform.
+fn get_sound_type(
+    dtype: Type,
+    archive: &mut SfxArchive,
+    reporter: &mut CliReporter,
+) -> anyhow::Result<SoundType> {
+    reporter.info("Detecting the platform of the sfx archive.");
+    let platform = archive.detect_platform()?;
+    match platform {
+        Some(platform) => reporter.good(format!("Detected platform: {platform:?}")),
+        None => reporter.warn("Can't detect the platform of the sfx archive."),
+    }
+
+    let sound_type = match dtype {
         Type::RawSound => SoundType::Raw,
+        #[cfg(all(feature = "pc", feature = "ps2"))]
+        Type::Wav => match platform {
+            Some(Platform::Pc) => SoundType::PcWav,
+            Some(Platform::Ps2) => SoundType::Ps2Wav,
+            None => anyhow::bail!("Can't pick the wav type, use pc-wav or ps2-wav instead."),
+        },
         #[cfg(feature = "pc")]
         Type::PcWav => SoundType::PcWav,
         #[cfg(feature = "ps2")]
@@ -153,5 +359,15 @@ fn get_sound_type(dtype: Type) -> SoundType {
         #[cfg(all(feature = "ps2", feature = "wav"))]
         Type::Ps2Wav => SoundType::Ps2Wav,
         _ => unreachable!(),
+    };
+
+    if let (Some(expected), Some(platform)) = (sound_type.platform(), platform) {
+        if expected != platform {
+            reporter.warn(format!(
+                "The sfx archive look like a {platform:?} archive, but {sound_type:?} is for {expected:?}, the sounds may end up as garbage."
+            ));
+        }
     }
+
+    Ok(sound_type)
 }
@@ -0,0 +1,88 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use clap::{Parser, ValueHint};
+use saamt_core::{
+    reporter::Logger,
+    stream::{beats::BeatDetector, structures::TrackMetadata},
+    utils::wav::Wav,
+};
+
+use crate::{commands::utils, reporter::CliReporter};
+
+#[derive(Debug, Parser)]
+pub struct BeatsCommand {
+    /// Input wav file
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    input: PathBuf,
+    /// Output json file, by default it will be saved next to the input with `.json` extension
+    output: Option<PathBuf>,
+    /// Lowest tempo to look for (beats per minute)
+    #[arg(long, default_value_t = 60.0, value_parser = parse_bpm)]
+    min_bpm: f32,
+    /// Highest tempo to look for (beats per minute)
+    #[arg(long, default_value_t = 180.0, value_parser = parse_bpm)]
+    max_bpm: f32,
+    /// Control values of the beats, they get assigned to the beats in a repeating pattern
+    #[arg(short, long, value_delimiter = ',', default_values_t = [1, 2, 3, 4])]
+    controls: Vec<i32>,
+}
+
+impl BeatsCommand {
+    pub fn command(self, mut reporter: CliReporter) -> anyhow::Result<()> {
+        reporter.info("Stream action: Beats");
+        anyhow::ensure!(
+            self.min_bpm < self.max_bpm,
+            "The lowest tempo ({}) should be lower then the highest tempo ({}).",
+            self.min_bpm,
+            self.max_bpm
+        );
+
+        reporter.info("Opening Wav file.");
+        let wav = Wav::from_file(&self.input)?;
+        reporter.good("Wav file loaded.");
+
+        reporter.info("Detecting beats.");
+        let detector = BeatDetector {
+            min_bpm: self.min_bpm,
+            max_bpm: self.max_bpm,
+            controls: self.controls,
+            ..Default::default()
+        };
+        let detected = detector.detect(&wav);
+        if detected.beats.is_empty() {
+            reporter.warn("No beat was detected.");
+        } else {
+            reporter.good(format!(
+                "Detected {} beats at {:.1} bpm.",
+                detected.beats.len(),
+                detected.bpm
+            ));
+        }
+
+        let output = self
+            .output
+            .unwrap_or_else(|| self.input.with_extension("json"));
+        let metadata = TrackMetadata {
+            beats: detected.beats,
+            lengths: None,
+        };
+        let mut writer = BufWriter::new(File::create(output)?);
+        serde_json::to_writer_pretty(&mut writer, &metadata)?;
+        writer.flush()?;
+
+        reporter.good("Beats saved.");
+
+        Ok(())
+    }
+}
+
+fn parse_bpm(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(bpm) if bpm.is_finite() && bpm > 0.0 => Ok(bpm),
+        _ => Err("You need to pass a finite tempo above zero.".to_owned()),
+    }
+}
@@ -1 +1,454 @@
+use std::{ffi::OsStr, path::PathBuf};
+#[cfg(feature = "serde")]
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
 
+use clap::{Args, Subcommand, ValueEnum, ValueHint};
+
+#[cfg(feature = "ogg")]
+use saamt_core::stream::platforms::pc::DEFAULT_OGG_QUALITY;
+#[cfg(feature = "serde")]
+use saamt_core::stream::station::StationManifest;
+use saamt_core::{reporter::Logger, stream::ImportOptions, stream_prelude::*};
+
+use crate::{commands::utils, reporter::CliReporter};
+
+#[cfg(all(feature = "wav", feature = "serde"))]
+mod beats;
+
+#[derive(Debug, Subcommand)]
+pub enum StreamCommands {
+    /// Export the tracks from stream archive
+    #[command(arg_required_else_help = true)]
+    Export {
+        #[command(flatten)]
+        archive: ArchiveArgs,
+        #[command(flatten)]
+        dtype: TypeArg,
+        output_folder: Option<PathBuf>,
+        /// Also export the metadata (beats and lengths) of each track as json
+        #[cfg(feature = "serde")]
+        #[arg(short, long)]
+        metadata: bool,
+    },
+    /// Import the files (and track_XXX.json metadata files) into stream archive
+    #[command(arg_required_else_help = true)]
+    Import {
+        #[command(flatten)]
+        archive: ArchiveArgs,
+        #[command(flatten)]
+        dtype: TypeArg,
+        #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+        files_folder: PathBuf,
+        output_file: Option<PathBuf>,
+        /// Where to save the updated lookup table, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        output_lookup: Option<PathBuf>,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    /// Build a whole radio station archive from a json manifest of songs, DJ lines and adverts
+    #[cfg(feature = "serde")]
+    #[command(arg_required_else_help = true)]
+    BuildStation {
+        /// Path to the json manifest, an ordered list of `{ "file", "kind", "metadata" }`
+        /// tracks with exactly one track for each lookup entry of the station, the game
+        /// refer to the tracks by their index so a manifest with more or less tracks fails
+        #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+        manifest: PathBuf,
+        /// Path to lookup table file (TrakLkup.dat)
+        #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+        lookup_table: PathBuf,
+        /// Name of the station stream archive (for example `CR`)
+        station: String,
+        /// Path of the new archive, by default it will be saved next to the manifest
+        output_file: Option<PathBuf>,
+        /// Optional path to pak names file (StrmPaks.dat)
+        #[arg(short, long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+        pak_names: Option<PathBuf>,
+        /// Platform of the station
+        #[arg(long, value_enum, default_value_t = PlatformArg::Pc)]
+        platform: PlatformArg,
+        /// Where to save the updated lookup table, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        output_lookup: Option<PathBuf>,
+        #[command(flatten)]
+        options: ImportArgs,
+    },
+    /// List the tracks of stream archive with their duration, sample rate, channels and size
+    #[command(arg_required_else_help = true, alias = "info")]
+    List {
+        #[command(flatten)]
+        archive: ArchiveArgs,
+        /// Platform of the stream archive
+        #[arg(long, value_enum, default_value_t = PlatformArg::Pc)]
+        platform: PlatformArg,
+        /// Also save the list as json to the given path
+        #[cfg(feature = "serde")]
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        json: Option<PathBuf>,
+    },
+    /// Check the ogg data of the tracks inside a PC stream archive for issues
+    #[command(arg_required_else_help = true)]
+    Validate {
+        #[command(flatten)]
+        archive: ArchiveArgs,
+    },
+    /// Convert the stream archive to the other platform (PC to PS2 or PS2 to PC)
+    #[cfg(all(feature = "ps2", feature = "ogg"))]
+    #[command(arg_required_else_help = true)]
+    Convert {
+        #[command(flatten)]
+        archive: ArchiveArgs,
+        /// Target platform, the stream archive should be from the other platform
+        #[arg(long, value_enum)]
+        to: PlatformArg,
+        output_file: Option<PathBuf>,
+        /// Where to save the updated lookup table, by default it will be saved next to
+        /// the original one with `.new.dat` extension
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        output_lookup: Option<PathBuf>,
+        /// Vorbis quality used for encoding the PC (ogg) tracks, ignored when converting to PS2,
+        /// between -0.1 (lowest) and 1.0 (highest)
+        #[arg(short, long, default_value_t = DEFAULT_OGG_QUALITY, allow_negative_numbers = true)]
+        quality: f32,
+    },
+    /// Detect the beats of a wav file and save them as a json metadata file next to it,
+    /// so track_XXX.wav gets the track_XXX.json that import looks for
+    #[cfg(all(feature = "wav", feature = "serde"))]
+    #[command(arg_required_else_help = true)]
+    Beats(beats::BeatsCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ArchiveArgs {
+    /// Path to the input file
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    input_archive: PathBuf,
+    /// Path to lookup table file (TrakLkup.dat)
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    lookup_table: PathBuf,
+    /// Optional path to pak names file (StrmPaks.dat)
+    #[arg(short, long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pak_names: Option<PathBuf>,
+}
+
+impl ArchiveArgs {
+    fn stream_manager(&self, reporter: &mut CliReporter) -> anyhow::Result<StreamManager> {
+        Ok(StreamManager::new(
+            &self.lookup_table,
+            self.pak_names.as_ref(),
+            reporter,
+        )?)
+    }
+
+    /// Default path of a new archive, `AA.new` or `<name>.<ext>.new` if the archive have extension.
+    fn default_output(&self) -> PathBuf {
+        let extension = self
+            .input_archive
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|e| e.to_owned() + ".")
+            .unwrap_or_default();
+        self.input_archive.with_extension(extension + "new")
+    }
+
+    /// Default path of the updated lookup table, next to the original one.
+    fn default_output_lookup(&self) -> PathBuf {
+        self.lookup_table.with_extension("new.dat")
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Generate the beats of the imported tracks from their audio,
+    /// track metadata still take priority
+    #[cfg(feature = "wav")]
+    #[arg(short, long)]
+    generate_beats: bool,
+    /// Vorbis quality used for encoding the wav tracks, between -0.1 (lowest) and 1.0 (highest)
+    #[cfg(feature = "ogg")]
+    #[arg(short, long, default_value_t = DEFAULT_OGG_QUALITY, allow_negative_numbers = true)]
+    quality: f32,
+    /// Check the imported ogg tracks and rewrite their pages into a game safe layout
+    /// if any issue found
+    #[cfg(feature = "ogg")]
+    #[arg(long)]
+    repair_ogg: bool,
+}
+
+impl From<ImportArgs> for ImportOptions {
+    fn from(value: ImportArgs) -> Self {
+        Self {
+            #[cfg(feature = "wav")]
+            beat_detector: value.generate_beats.then(Default::default),
+            #[cfg(feature = "ogg")]
+            ogg_quality: value.quality,
+            #[cfg(feature = "ogg")]
+            repair_ogg: value.repair_ogg,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct TypeArg {
+    /// Export/Import data type
+    #[arg(short = 't', long = "type", name = "TYPE", value_enum, default_value_t = Type::Ogg)]
+    dtype: Type,
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum Type {
+    /// Export/Import PC tracks as Ogg
+    #[default]
+    Ogg,
+    /// Export/Import PC tracks as Wav
+    #[cfg(feature = "ogg")]
+    #[value(alias = "wav")]
+    PcWav,
+    /// Export/Import PS2 tracks as stereo Wav
+    #[cfg(all(feature = "ps2", feature = "wav"))]
+    Ps2Wav,
+}
+
+#[derive(Clone, Debug, ValueEnum)]
+pub enum PlatformArg {
+    Pc,
+    Ps2,
+}
+
+impl From<PlatformArg> for Platform {
+    fn from(value: PlatformArg) -> Self {
+        match value {
+            PlatformArg::Pc => Platform::Pc,
+            PlatformArg::Ps2 => Platform::Ps2,
+        }
+    }
+}
+
+impl StreamCommands {
+    pub fn command(self, mut reporter: CliReporter) -> anyhow::Result<()> {
+        match self {
+            StreamCommands::Export {
+                archive,
+                dtype,
+                output_folder,
+                #[cfg(feature = "serde")]
+                metadata,
+            } => {
+                reporter.info("Stream action: Export");
+                let stream = archive.stream_manager(&mut reporter)?;
+                let track_type = get_track_type(dtype.dtype);
+                let platform = track_type.platform();
+                reporter.info(format!("Track type: {track_type:?}"));
+
+                // PC stream archives don't have any extension, so we can't just remove it
+                let output_dir =
+                    output_folder.unwrap_or_else(|| archive.input_archive.with_extension("tracks"));
+
+                stream
+                    .load(&archive.input_archive, platform, &mut reporter)?
+                    .tracks()
+                    .export_all_tracks(track_type, &output_dir, &mut reporter)?;
+
+                #[cfg(feature = "serde")]
+                if metadata {
+                    stream
+                        .load(&archive.input_archive, platform, &mut reporter)?
+                        .tracks()
+                        .export_all_metadata(&output_dir, &mut reporter)?;
+                }
+
+                reporter.good("Export finished.");
+            }
+            StreamCommands::Import {
+                archive,
+                dtype,
+                files_folder,
+                output_file,
+                output_lookup,
+                options,
+            } => {
+                reporter.info("Stream action: Import");
+                let mut stream = archive.stream_manager(&mut reporter)?;
+                let track_type = get_track_type(dtype.dtype);
+                let platform = track_type.platform();
+                reporter.info(format!("Track type: {track_type:?}"));
+
+                let mut loaded = stream.load(&archive.input_archive, platform, &mut reporter)?;
+                loaded.set_import_options(options.into());
+
+                loaded.import_tracks(
+                    track_type,
+                    files_folder,
+                    output_file.unwrap_or_else(|| archive.default_output()),
+                    &mut stream.lookup_table,
+                    &mut reporter,
+                )?;
+
+                reporter.info("Saving the updated lookup table.");
+                let output_lookup =
+                    output_lookup.unwrap_or_else(|| archive.default_output_lookup());
+                stream.update_lookup(Some(output_lookup))?;
+                reporter.good("Lookup table saved.");
+
+                reporter.good("Import finished.");
+            }
+            #[cfg(feature = "serde")]
+            StreamCommands::BuildStation {
+                manifest,
+                lookup_table,
+                station,
+                output_file,
+                pak_names,
+                platform,
+                output_lookup,
+                options,
+            } => {
+                reporter.info("Stream action: Build station");
+                let mut stream =
+                    StreamManager::new(&lookup_table, pak_names.as_ref(), &mut reporter)?;
+
+                reporter.info("Loading station manifest.");
+                let station_manifest = StationManifest::from_file(&manifest)?;
+                reporter.good("Station manifest loaded.");
+
+                let output = output_file.unwrap_or_else(|| manifest.with_file_name(&station));
+                stream.build_station(
+                    &station,
+                    &station_manifest,
+                    output,
+                    platform.into(),
+                    &options.into(),
+                    &mut reporter,
+                )?;
+
+                reporter.info("Saving the updated lookup table.");
+                let output_lookup =
+                    output_lookup.unwrap_or_else(|| lookup_table.with_extension("new.dat"));
+                stream.update_lookup(Some(output_lookup))?;
+                reporter.good("Lookup table saved.");
+
+                reporter.good("Build station finished.");
+            }
+            StreamCommands::List {
+                archive,
+                platform,
+                #[cfg(feature = "serde")]
+                json,
+            } => {
+                reporter.info("Stream action: List");
+                let infos = archive
+                    .stream_manager(&mut reporter)?
+                    .load(&archive.input_archive, platform.into(), &mut reporter)?
+                    .tracks_info(&mut reporter)?;
+
+                for info in &infos {
+                    reporter.info(format!(
+                        "track_{:03} (lookup index {}): {}, {}hz, {} channels, {} bytes",
+                        info.index,
+                        info.lookup_index,
+                        format_duration(info.duration),
+                        info.sample_rate,
+                        info.channels,
+                        info.size
+                    ));
+                }
+                reporter.good(format!(
+                    "{} tracks, total duration: {}",
+                    infos.len(),
+                    format_duration(infos.iter().map(|i| i.duration).sum())
+                ));
+
+                #[cfg(feature = "serde")]
+                if let Some(json) = json {
+                    let mut writer = BufWriter::new(File::create(json)?);
+                    serde_json::to_writer_pretty(&mut writer, &infos)?;
+                    writer.flush()?;
+                    reporter.good("Track list saved as json.");
+                }
+            }
+            StreamCommands::Validate { archive } => {
+                reporter.info("Stream action: Validate");
+                let result = archive
+                    .stream_manager(&mut reporter)?
+                    .load(&archive.input_archive, Platform::Pc, &mut reporter)?
+                    .validate_tracks(&mut reporter)?;
+
+                for (index, issues) in &result {
+                    for issue in issues {
+                        reporter.warn(format!("track_{index:03}: {issue}"));
+                    }
+                }
+
+                if result.is_empty() {
+                    reporter.good("No issue found.");
+                } else {
+                    reporter.warn(format!("{} tracks have issues.", result.len()));
+                }
+            }
+            #[cfg(all(feature = "ps2", feature = "ogg"))]
+            StreamCommands::Convert {
+                archive,
+                to,
+                output_file,
+                output_lookup,
+                quality,
+            } => {
+                reporter.info("Stream action: Convert");
+                let mut stream = archive.stream_manager(&mut reporter)?;
+                let target = Platform::from(to);
+                let source = match target {
+                    Platform::Pc => Platform::Ps2,
+                    Platform::Ps2 => Platform::Pc,
+                };
+
+                let mut loaded = stream.load(&archive.input_archive, source, &mut reporter)?;
+                loaded.set_ogg_quality(quality);
+                loaded.convert(
+                    target,
+                    output_file.unwrap_or_else(|| archive.default_output()),
+                    &mut stream.lookup_table,
+                    &mut reporter,
+                )?;
+
+                reporter.info("Saving the updated lookup table.");
+                let output_lookup =
+                    output_lookup.unwrap_or_else(|| archive.default_output_lookup());
+                stream.update_lookup(Some(output_lookup))?;
+                reporter.good("Lookup table saved.");
+
+                reporter.good("Convert finished.");
+            }
+            #[cfg(all(feature = "wav", feature = "serde"))]
+            StreamCommands::Beats(c) => c.command(reporter)?,
+        }
+        Ok(())
+    }
+}
+
+/// Format the duration (in seconds) as `hh:mm:ss.mmm`.
+fn format_duration(duration: f64) -> String {
+    let millis = (duration * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn get_track_type(dtype: Type) -> TrackType {
+    match dtype {
+        Type::Ogg => TrackType::Ogg,
+        #[cfg(feature = "ogg")]
+        Type::PcWav => TrackType::PcWav,
+        #[cfg(all(feature = "ps2", feature = "wav"))]
+        Type::Ps2Wav => TrackType::Ps2Wav,
+    }
+}
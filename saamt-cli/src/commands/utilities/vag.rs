@@ -3,7 +3,10 @@ use std::path::PathBuf;
 use clap::{Parser, Subcommand, ValueEnum, ValueHint};
 use saamt_core::{
     reporter::Logger,
-    utils::vag::{encoder::LoopMode as ILoopMode, VagAudio},
+    utils::vag::{
+        encoder::{LoopMode as ILoopMode, WAV2VAGEncoder},
+        VagAudio,
+    },
 };
 
 #[cfg(all(target_os = "windows", feature = "ps2-export-mfaudio"))]
@@ -43,6 +46,9 @@ enum Action {
         /// What loop mode to use when encoding wav to vag
         #[arg(short = 'o', long, value_enum, default_value_t = LoopMode::FromInput)]
         loop_mode: LoopMode,
+        /// Add TPDF dither when converting 24/32-bit and float wav files to 16-bit
+        #[arg(long)]
+        dither: bool,
     },
 }
 
@@ -108,7 +114,7 @@ impl VagCommands {
                 vag.to_wav().to_disc(output)?;
                 reporter.good("Wav saved to disk.");
             }
-            Action::ToVag { loop_mode } => {
+            Action::ToVag { loop_mode, dither } => {
                 #[cfg(all(target_os = "windows", feature = "ps2-export-mfaudio"))]
                 if self.use_mfaudio {
                     if !std::path::Path::new(r"MFAudio.exe").is_file() {
@@ -123,7 +129,8 @@ impl VagCommands {
                 }
 
                 reporter.info("Opening Wav file.");
-                let vag = VagAudio::from_wav(self.input, loop_mode.into())?;
+                let vag = WAV2VAGEncoder::new_with_dither(&self.input, loop_mode.into(), dither)?
+                    .generate_vag();
                 reporter.good("Wav file loaded.");
 
                 reporter.info("Encoding and saving Wav to Vag and save it to disk.");
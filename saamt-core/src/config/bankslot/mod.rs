@@ -6,7 +6,7 @@ use std::{
 
 use binrw::{binrw, BinRead, BinWrite};
 
-use crate::error::*;
+use crate::{config::lookuptable::LookUpTable, error::*};
 
 /// # Bank Slot
 /// hold all banks slots.
@@ -71,6 +71,75 @@ impl BankSlot {
             offset += size;
         }
     }
+
+    /// Grow the buffer of the slots so they can still hold the banks after their size
+    /// changed from the `original` lookup table to the `updated` one.
+    ///
+    /// Which bank get loaded into which slot is decided by the game at runtime, so each bank
+    /// is considered to land in every slot that was big enough for its original size and
+    /// the slot get the size of the largest of them. Entries that doesn't exist in the
+    /// `original` lookup table (new banks) could land in any slot, so at least the largest
+    /// slot get grown to fit them. Slots never get smaller.
+    ///
+    /// Returns the slots that got bigger.
+    pub fn fit_banks(&mut self, original: &LookUpTable, updated: &LookUpTable) -> Vec<SlotChange> {
+        let banks: Vec<_> = original
+            .iter()
+            .zip(updated.iter())
+            .map(|(original, updated)| (original.length, updated.length))
+            .filter(|(original, updated)| updated > original)
+            .collect();
+
+        let mut sizes: Vec<u32> = self
+            .slots
+            .iter()
+            .map(|slot| {
+                banks
+                    .iter()
+                    .filter(|&&(original, _)| original <= slot.size)
+                    .map(|&(_, updated)| updated)
+                    .fold(slot.size, u32::max)
+            })
+            .collect();
+
+        let new_banks = updated.iter().skip(original.len()).map(|e| e.length).max();
+        if let Some(new_banks) = new_banks {
+            let largest = sizes
+                .iter_mut()
+                .reduce(|largest, size| if *size > *largest { size } else { largest });
+            if let Some(largest) = largest {
+                *largest = (*largest).max(new_banks);
+            }
+        }
+
+        let changes = self
+            .slots
+            .iter()
+            .zip(&sizes)
+            .enumerate()
+            .filter(|(_, (slot, &size))| slot.size != size)
+            .map(|(index, (slot, &size))| SlotChange {
+                index,
+                old_size: slot.size,
+                new_size: size,
+            })
+            .collect();
+
+        self.update_buf_sizes(sizes);
+
+        changes
+    }
+}
+
+/// Buffer size change of a slot, see [`BankSlot::fit_banks`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotChange {
+    /// Index of the slot.
+    pub index: usize,
+    /// Buffer size before the change.
+    pub old_size: u32,
+    /// Buffer size after the change.
+    pub new_size: u32,
 }
 
 impl Debug for BankSlot {
@@ -107,4 +176,49 @@ mod test {
 
         assert!(bs.is_ok());
     }
+
+    #[test]
+    fn fit_banks() {
+        let mut bs = BankSlot::from_reader(&mut Cursor::new(include_bytes!(
+            r"../../../test-assets/BankSlot.dat"
+        )))
+        .unwrap();
+        let original = LookUpTable::from_reader(&mut Cursor::new(include_bytes!(
+            "../../../test-assets/BankLkup.dat"
+        )))
+        .unwrap();
+        let sizes = bs.export_buf_sizes();
+
+        let mut updated = original.clone();
+        assert!(bs.fit_banks(&original, &updated).is_empty());
+        assert_eq!(bs.export_buf_sizes(), sizes);
+
+        // first FEET bank (90998 bytes) only fit inside the slots that are bigger then it
+        updated.get_mut(0).unwrap().length = 2_000_000;
+        let changes = bs.fit_banks(&original, &updated);
+        let new_sizes = bs.export_buf_sizes();
+
+        assert_eq!(changes.len(), sizes.iter().filter(|&&s| s >= 90998).count());
+        for change in &changes {
+            assert_eq!(change.old_size, sizes[change.index]);
+            assert_eq!(change.new_size, 2_000_000);
+        }
+        for (old, new) in sizes.iter().zip(&new_sizes) {
+            assert_eq!(*new, if *old >= 90998 { 2_000_000 } else { *old });
+        }
+        assert_eq!(bs.slots[1].offset, bs.slots[0].offset + new_sizes[0]);
+
+        // new banks only need to fit inside the largest slot
+        let original = updated.clone();
+        let entry = *updated.first().unwrap();
+        let index = updated.push(entry);
+        assert!(bs.fit_banks(&original, &updated).is_empty());
+
+        updated.get_mut(index).unwrap().length = 3_000_000;
+        let changes = bs.fit_banks(&original, &updated);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].old_size, 2_000_000);
+        assert_eq!(changes[0].new_size, 3_000_000);
+        assert_eq!(bs.export_buf_sizes().into_iter().max(), Some(3_000_000));
+    }
 }
@@ -12,7 +12,7 @@ use crate::error::*;
 
 /// ## LookupTable
 /// GTASA lookup table, hold all LookupEntries.
-#[derive(Debug, Clone, BinRead, BinWrite)]
+#[derive(Debug, Clone, PartialEq, Eq, BinRead, BinWrite)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[brw(little)]
 pub struct LookUpTable {
@@ -23,7 +23,7 @@ pub struct LookUpTable {
 /// ## LookupEntry
 /// LookupEntry represents an entry in the lookup table. It contains the index,
 /// offset and length for the data.
-#[derive(Debug, Clone, Copy, BinRead, BinWrite)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinRead, BinWrite)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[brw(little)]
 pub struct LookUpEntry {
@@ -33,6 +33,18 @@ pub struct LookUpEntry {
     pub length: u32,
 }
 
+impl LookUpEntry {
+    /// Create a new [`LookUpEntry`] for the pak with the given index.
+    pub fn new(index: u8, offset: u32, length: u32) -> Self {
+        Self {
+            index,
+            padding: [0; 3],
+            offset,
+            length,
+        }
+    }
+}
+
 impl LookUpTable {
     /// Read and parse the [`LookUpTable`] from the reader.
     pub fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self> {
@@ -49,6 +61,12 @@ impl LookUpTable {
         self.entries.get_mut(index)
     }
 
+    /// Add the entry to the end of the [`LookUpTable`] and return its index.
+    pub fn push(&mut self, entry: LookUpEntry) -> usize {
+        self.entries.push(entry);
+        self.entries.len() - 1
+    }
+
     /// Count how many entry inside the [`LookUpTable`] match the given index.
     pub fn count_entries_matching_pak_idx(&self, idx: u8) -> usize {
         self.entries.iter().filter(|e| e.index == idx).count()
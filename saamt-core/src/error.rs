@@ -19,6 +19,18 @@ pub enum Error {
     #[error(transparent)]
     Wav(#[from] hound::Error),
 
+    #[cfg(feature = "ogg")]
+    #[error(transparent)]
+    VorbisDecode(#[from] lewton::VorbisError),
+
+    #[cfg(feature = "ogg")]
+    #[error(transparent)]
+    VorbisEncode(#[from] vorbis_rs::VorbisError),
+
+    #[cfg(feature = "serde")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
     #[error("No {0} file found in input folder")]
     NoFileFound(&'static str),
 
@@ -48,6 +60,9 @@ pub enum Error {
     #[error("Unsorted sfx banks, tool expect the bank entries to be back to back")]
     UnsortedSfxBanks,
 
+    #[error("Unsorted stream tracks, tool expect the track entries to be back to back")]
+    UnsortedStreamTracks,
+
     #[cfg(all(target_os = "windows", feature = "ps2-export-mfaudio"))]
     #[error("There was a error in wav worker thread: {0}")]
     WavWorkerThreadError(String),
@@ -60,6 +75,58 @@ pub enum Error {
     #[error("Found an invalid sound data when trying to convert to wav")]
     InvalidWavSoundData,
 
+    #[error("Can't find bank {0} in the sfx archive")]
+    BankNotFound(usize),
+
+    #[error("Can't find sound {sound} in bank {bank}")]
+    SoundNotFound { bank: usize, sound: usize },
+
+    #[error("Can't add sound {sound} to bank {bank}, the bank have {count} sounds and new sounds should come right after them")]
+    SoundIndexGap {
+        bank: usize,
+        sound: usize,
+        count: usize,
+    },
+
+    #[error("Bank can't have more then 400 sound entries, but it had {0}")]
+    TooManySoundEntries(usize),
+
+    #[error("Sample rate {0} isn't supported, sound sample rate should be between 1 and 65535")]
+    UnsupportedSampleRate(u32),
+
     #[error("Can't find index in Lookup Table")]
     CantFindIndexInLookUpTable,
+
+    #[error("This track type can't be used with {0:?} stream archives")]
+    UnsupportedTrackType(crate::platform::Platform),
+
+    #[error("Stream archive is already for {0:?}")]
+    SameStreamPlatform(crate::platform::Platform),
+
+    #[error("Sfx archive is already for {0:?}")]
+    SameSfxPlatform(crate::platform::Platform),
+
+    #[error("Can't detect the platform of the sfx archive")]
+    UnknownSfxPlatform,
+
+    #[error("Invalid ogg data: {0}")]
+    InvalidOgg(String),
+
+    #[error("Invalid vag data: {0}")]
+    InvalidVag(String),
+
+    #[error("Station {station} have {expected} tracks in the lookup table, but the manifest had {found}, the manifest need exactly one track for each of them")]
+    StationTrackCount {
+        station: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error(
+        "Can't use \"{0}\" as a station track, the file type isn't supported for this platform"
+    )]
+    UnsupportedStationFile(String),
+
+    #[error("Track can't have more then 1000 beat entries, but it had {0}")]
+    TooManyBeatEntries(usize),
 }
@@ -2,14 +2,24 @@
 
 pub mod config;
 pub mod error;
+pub mod platform;
 pub mod reporter;
 pub mod sfx;
 pub mod stream;
 
 pub mod utils;
 
+#[cfg(test)]
+mod test_utils;
+
 pub mod sfx_prelude {
     pub use crate::sfx::sound::SoundType;
     pub use crate::sfx::SfxManager;
     pub use crate::utils::helpers::DataSaveAll;
 }
+
+pub mod stream_prelude {
+    pub use crate::platform::Platform;
+    pub use crate::stream::track::TrackType;
+    pub use crate::stream::StreamManager;
+}
@@ -0,0 +1,10 @@
+//! Platforms that the toolkit know about.
+
+/// The platform that an archive belong to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Platform {
+    #[default]
+    Pc,
+    Ps2,
+}
@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{BufReader, BufWriter, Read, Seek, Write},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
@@ -9,14 +9,20 @@ use binrw::{BinRead, BinWrite};
 use crate::{
     error::*,
     config::lookuptable::LookUpEntry,
+    platform::Platform,
     reporter::{ProgressReport, ProgressReporterIterator},
 };
 
+#[cfg(feature = "serde")]
+use super::structures::BankMetadata;
 use super::{
     sound::{RawSounds, SoundType},
     structures::BankHeader,
 };
 
+/// Max number of banks that get read for detecting the platform of an archive.
+const DETECT_PLATFORM_BANKS: usize = 8;
+
 /// `Banks` struct loads banks from an SFX archive lazily.
 pub struct Banks {
     lookup: Vec<(usize, LookUpEntry)>,
@@ -55,6 +61,30 @@ impl Banks {
         self.len() == 0
     }
 
+    /// Guess the [`Platform`] of the archive from the sounds of its first 8 banks,
+    /// see [`RawSound::detect_platform`](super::sound::RawSound::detect_platform).
+    ///
+    /// The reader get seeked back to where it was, so the banks can still be used.
+    /// Returns `None` if none of the sounds had a known platform.
+    pub fn detect_platform(&mut self) -> Result<Option<Platform>> {
+        let start = self.reader.stream_position()?;
+
+        let mut votes = PlatformVotes::default();
+        for (index, entry) in self
+            .lookup
+            .iter()
+            .skip(self.lookup_idx)
+            .take(DETECT_PLATFORM_BANKS)
+        {
+            let bank = read_bank(&mut self.reader, *index, entry)?;
+            votes.add(&bank);
+        }
+
+        self.reader.seek(SeekFrom::Start(start))?;
+
+        Ok(votes.platform())
+    }
+
     /// Exports all banks from the SFX archive to the given output directory.
     ///
     /// Iterates over each bank, exporting it to a .bnk file in the output
@@ -96,7 +126,8 @@ impl Banks {
     /// bank index.
     ///
     /// Sounds are named `sound_YYY.ext` where `YYY` is the sound index and `.ext` is the extension
-    /// for the given sound type.
+    /// for the given sound type, with the `serde` feature the [metadata](super::structures::BankMetadata)
+    /// of each bank also get saved as `sounds.json` inside its subdir.
     ///
     /// Reports progress using the given progress reporter.
     pub fn export_all_sounds(
@@ -139,12 +170,36 @@ impl Banks {
 
                 writer.flush()?;
             }
+
+            // the entries (loop offset, headroom, ...) can't be stored in most of the types,
+            // so we save them next to the sounds
+            #[cfg(feature = "serde")]
+            {
+                let mut writer =
+                    BufWriter::new(File::create(output_dir.join(super::BANK_METADATA_NAME))?);
+                serde_json::to_writer_pretty(&mut writer, &BankMetadata::from(&bank.header))?;
+                writer.flush()?;
+            }
         }
 
         Ok(())
     }
 }
 
+/// Read a single bank with the given lookup `index` and `entry` from the current
+/// position of the reader.
+fn read_bank(reader: &mut BufReader<File>, index: usize, entry: &LookUpEntry) -> Result<Bank> {
+    let header = BankHeader::read_args(reader, entry.length as usize)?;
+    let mut bytes = vec![0_u8; entry.length as usize];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(Bank {
+        index,
+        header,
+        bytes,
+    })
+}
+
 /// BanksIter is an iterator that lazily iterates over the banks in an SFX
 /// archive.
 ///
@@ -164,22 +219,7 @@ impl Iterator for BanksIter {
         let (index, entry) = self.lookup.get(self.lookup_idx)?;
         self.lookup_idx += 1;
 
-        Some(match BankHeader::read_args(&mut self.reader, entry.length as usize) {
-            Ok(header) => {
-                let mut bytes = vec![0_u8; entry.length as usize];
-
-                if let Err(e) = self.reader.read_exact(&mut bytes) {
-                    return Some(Err(Error::Io(e)));
-                }
-
-                Ok(Bank {
-                    index: *index,
-                    header,
-                    bytes,
-                })
-            }
-            Err(e) => Err(Error::BinRw(e)),
-        })
+        Some(read_bank(&mut self.reader, *index, entry))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -225,4 +265,41 @@ impl Bank {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Guess the [`Platform`] of the bank based on the platform that most of its
+    /// sounds look like, see [`RawSound::detect_platform`](super::sound::RawSound::detect_platform).
+    ///
+    /// Returns `None` if none of the sounds had a known platform.
+    pub fn detect_platform(&self) -> Option<Platform> {
+        let mut votes = PlatformVotes::default();
+        votes.add(self);
+        votes.platform()
+    }
+}
+
+/// Number of the sounds that look like each platform.
+#[derive(Default)]
+struct PlatformVotes {
+    pc: usize,
+    ps2: usize,
+}
+
+impl PlatformVotes {
+    fn add(&mut self, bank: &Bank) {
+        for sound in bank.raw_sounds() {
+            match sound.detect_platform() {
+                Some(Platform::Pc) => self.pc += 1,
+                Some(Platform::Ps2) => self.ps2 += 1,
+                None => (),
+            }
+        }
+    }
+
+    fn platform(&self) -> Option<Platform> {
+        match (self.pc, self.ps2) {
+            (0, 0) => None,
+            (pc, ps2) if ps2 > pc => Some(Platform::Ps2),
+            _ => Some(Platform::Pc),
+        }
+    }
 }
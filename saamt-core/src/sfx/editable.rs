@@ -0,0 +1,513 @@
+//! Editable in-memory model of a sfx archive.
+//!
+//! Unlike [`SfxArchive::import_sounds`] that read the sounds from a folder, this let you
+//! change the sounds and their entries directly and save the archive when you are done.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Cursor, Write},
+    path::Path,
+};
+
+use crate::{
+    config::lookuptable::{LookUpEntry, LookUpTable},
+    error::*,
+    platform::Platform,
+    reporter::{ProgressReport, ProgressReporterIterator},
+};
+
+use super::{
+    bank::Bank,
+    structures::{BankHeader, SoundEntry, MAX_SOUND_ENTRIES, NO_LOOP},
+    SfxArchive,
+};
+#[cfg(any(feature = "ps2", feature = "pc"))]
+use super::{platforms, sound::SoundType, ImportOptions};
+
+/// Sample rate of the new sounds that get added to an empty bank.
+const DEFAULT_SAMPLE_RATE: u16 = 22050;
+/// Data of a blanked PC sound, 8 silent PCM samples.
+const BLANK_PC_SOUND: [u8; 16] = [0; 16];
+/// Data of a blanked PS2 sound, the 16 zero bytes that PS2 sounds start with, a silent
+/// ADPCM block with the last block flag (`1`) and the terminating block that end the
+/// playback (`7`), the same way the sounds of the game end.
+const BLANK_PS2_SOUND: [u8; 48] = {
+    let mut bytes = [0; 48];
+    bytes[16 + 1] = 1;
+    bytes[32 + 1] = 7;
+    bytes
+};
+
+/// A sfx archive loaded fully into memory, see [`SfxArchive::into_editable`].
+pub struct EditableSfxArchive {
+    banks: Vec<EditableBank>,
+}
+
+/// A bank of an [`EditableSfxArchive`], each sound keep its own data so they can be
+/// replaced without caring about the offsets.
+pub struct EditableBank {
+    /// index of bank inside the lookup index
+    pub index: usize,
+    /// Index of the bank entry inside the lookup table.
+    lookup_index: usize,
+    pub sounds: Vec<EditableSound>,
+}
+
+/// A sound of an [`EditableBank`].
+///
+/// The `offset` and `size` of the entry are ignored, they get calculated from the data
+/// when the bank get saved.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditableSound {
+    pub entry: SoundEntry,
+    pub bytes: Vec<u8>,
+}
+
+impl SfxArchive {
+    /// Load all the banks of the archive into memory and return an [`EditableSfxArchive`].
+    ///
+    /// Reports progress using the given progress reporter.
+    pub fn into_editable(self, reporter: &mut impl ProgressReport) -> Result<EditableSfxArchive> {
+        let len = self.banks.len();
+        let banks = self
+            .banks
+            .banks_iter()
+            .zip(self.indexes)
+            .progress_report(reporter, len, "Loading banks".to_owned())
+            .map(|(bank, lookup_index)| Ok(EditableBank::from_bank(bank?, lookup_index)))
+            .collect::<Result<_>>()?;
+
+        Ok(EditableSfxArchive { banks })
+    }
+}
+
+impl EditableSfxArchive {
+    /// Returns the banks of the archive, in the same order as they are in the archive.
+    pub fn banks(&self) -> &[EditableBank] {
+        &self.banks
+    }
+
+    /// Returns the bank with the given index.
+    pub fn bank(&self, bank: usize) -> Option<&EditableBank> {
+        self.banks.iter().find(|b| b.index == bank)
+    }
+
+    /// Returns a mutable reference to the bank with the given index.
+    pub fn bank_mut(&mut self, bank: usize) -> Option<&mut EditableBank> {
+        self.banks.iter_mut().find(|b| b.index == bank)
+    }
+
+    /// Returns a mutable reference to the sound inside the bank.
+    pub fn sound_mut(&mut self, bank: usize, sound: usize) -> Result<&mut EditableSound> {
+        self.bank_mut(bank)
+            .ok_or(Error::BankNotFound(bank))?
+            .sounds
+            .get_mut(sound)
+            .ok_or(Error::SoundNotFound { bank, sound })
+    }
+
+    /// Add a new empty bank to the end of the archive and return it, a matching entry for
+    /// the bank get added to the end of the `lookuptbl`.
+    ///
+    /// The entry is added to the end of the lookup table so the index of the other entries,
+    /// that the game use to refer to the banks, doesn't change.
+    ///
+    /// # Note:
+    /// the lookup table isn't saved, you need to call [`super::SfxManager::update_lookup`]
+    /// after saving the archive.
+    pub fn add_bank(&mut self, lookuptbl: &mut LookUpTable) -> Result<&mut EditableBank> {
+        let Some(pak_index) = self
+            .banks
+            .first()
+            .and_then(|b| lookuptbl.get(b.lookup_index))
+            .map(|e| e.index)
+        else {
+            return Err(Error::CantFindIndexInLookUpTable);
+        };
+
+        let lookup_index = lookuptbl.push(LookUpEntry::new(pak_index, 0, 0));
+        let index = lookuptbl.count_entries_matching_pak_idx(pak_index) - 1;
+
+        let len = self.banks.len();
+        self.banks.push(EditableBank {
+            index,
+            lookup_index,
+            sounds: Vec::new(),
+        });
+
+        Ok(&mut self.banks[len])
+    }
+
+    /// Replace the data of the sound inside the bank with the given raw data,
+    /// the entry of the sound (like the sample rate) stay the same.
+    pub fn replace_sound(&mut self, bank: usize, sound: usize, data: Vec<u8>) -> Result<()> {
+        self.sound_mut(bank, sound)?.bytes = data;
+        Ok(())
+    }
+
+    /// Replace the sound inside the bank with a short silence, see [`EditableBank::blank_sound`].
+    pub fn blank_sound(&mut self, bank: usize, sound: usize, platform: Platform) -> Result<()> {
+        self.bank_mut(bank)
+            .ok_or(Error::BankNotFound(bank))?
+            .blank_sound(sound, platform)
+    }
+
+    /// Remove the sound from the bank, see [`EditableBank::remove_sound`].
+    pub fn remove_sound(&mut self, bank: usize, sound: usize) -> Result<EditableSound> {
+        self.bank_mut(bank)
+            .ok_or(Error::BankNotFound(bank))?
+            .remove_sound(sound)
+    }
+
+    /// Import the file at `path` as the given [`SoundType`] into the sound inside the bank,
+    /// see [`EditableBank::import_sound`].
+    ///
+    /// Returns true if the imported file wasn't mono.
+    #[cfg(any(feature = "ps2", feature = "pc"))]
+    pub fn import_sound(
+        &mut self,
+        bank: usize,
+        sound: usize,
+        sound_type: &SoundType,
+        path: impl AsRef<Path>,
+        options: &ImportOptions,
+    ) -> Result<bool> {
+        self.bank_mut(bank)
+            .ok_or(Error::BankNotFound(bank))?
+            .import_sound(sound, sound_type, path.as_ref(), options)
+    }
+
+    /// Save the archive to `output` and update the offset and length of the banks
+    /// inside the `lookuptbl`.
+    pub fn save(&self, output: impl AsRef<Path>, lookuptbl: &mut LookUpTable) -> Result<()> {
+        let mut writer = BufWriter::with_capacity(1024 * 1024, File::create(output)?);
+        let mut offset = 0;
+
+        for bank in &self.banks {
+            let Some(entry) = lookuptbl.get_mut(bank.lookup_index) else {
+                return Err(Error::CantFindIndexInLookUpTable);
+            };
+
+            let bank = bank.to_bank()?;
+
+            entry.offset = offset;
+            entry.length = bank.bytes.len() as u32;
+            offset += bank.len() as u32;
+
+            bank.to_writer(&mut writer)?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl EditableBank {
+    /// Split the data of the bank into its sounds.
+    pub(crate) fn from_bank(bank: Bank, lookup_index: usize) -> Self {
+        let sounds = bank
+            .header
+            .sound_entries
+            .iter()
+            .map(|entry| {
+                let start = entry.offset as usize;
+                EditableSound {
+                    entry: *entry,
+                    bytes: bank.bytes[start..start + entry.size].to_vec(),
+                }
+            })
+            .collect();
+
+        Self {
+            index: bank.index,
+            lookup_index,
+            sounds,
+        }
+    }
+
+    /// Index of the bank entry inside the lookup table.
+    pub fn lookup_index(&self) -> usize {
+        self.lookup_index
+    }
+
+    /// Create a new empty sound, the sample rate and headroom are the same as the last
+    /// sound of the bank.
+    pub fn new_sound(&self) -> EditableSound {
+        let (sample_rate, headroom) = self.sounds.last().map_or((DEFAULT_SAMPLE_RATE, 0), |s| {
+            (s.entry.sample_rate, s.entry.headroom)
+        });
+
+        EditableSound {
+            entry: SoundEntry::new(0, sample_rate, headroom),
+            bytes: Vec::new(),
+        }
+    }
+
+    /// Add the sound to the end of the bank, returns the index of the new sound.
+    pub fn push_sound(&mut self, sound: EditableSound) -> Result<usize> {
+        if self.sounds.len() >= MAX_SOUND_ENTRIES {
+            return Err(Error::TooManySoundEntries(self.sounds.len() + 1));
+        }
+
+        self.sounds.push(sound);
+        Ok(self.sounds.len() - 1)
+    }
+
+    /// Replace the data of the sound with a short silence that is valid for the `platform`
+    /// of the archive and disable its loop.
+    ///
+    /// The sound keep its index, so this is the safe way to free the memory of a sound
+    /// that the game still refer to.
+    pub fn blank_sound(&mut self, sound: usize, platform: Platform) -> Result<()> {
+        let index = self.index;
+        let sound = self
+            .sounds
+            .get_mut(sound)
+            .ok_or(Error::SoundNotFound { bank: index, sound })?;
+
+        sound.entry.loop_offset = NO_LOOP;
+        sound.bytes = match platform {
+            Platform::Pc => BLANK_PC_SOUND.to_vec(),
+            Platform::Ps2 => BLANK_PS2_SOUND.to_vec(),
+        };
+
+        Ok(())
+    }
+
+    /// Remove the sound from the bank and return it.
+    ///
+    /// # Note:
+    /// all the sounds after the removed one get shifted down by one index, use
+    /// [`EditableBank::blank_sound`] or [`EditableBank::truncate`] if the game refer
+    /// to them by their index.
+    pub fn remove_sound(&mut self, sound: usize) -> Result<EditableSound> {
+        if sound >= self.sounds.len() {
+            return Err(Error::SoundNotFound {
+                bank: self.index,
+                sound,
+            });
+        }
+
+        Ok(self.sounds.remove(sound))
+    }
+
+    /// Keep the first `len` sounds of the bank and drop the rest,
+    /// the index of the remaining sounds doesn't change.
+    pub fn truncate(&mut self, len: usize) {
+        self.sounds.truncate(len);
+    }
+
+    /// Import the file at `path` as the given [`SoundType`] into the sound with the given
+    /// index, the sample rate of the sound get updated for every type except `Raw`.
+    /// Wav files get mixed down to mono and resampled based on the `options`.
+    ///
+    /// If the index is the same as the number of the sounds, a new sound get added to
+    /// the end of the bank (see [`EditableBank::new_sound`]).
+    ///
+    /// Returns true if the imported file wasn't mono.
+    #[cfg(any(feature = "ps2", feature = "pc"))]
+    pub fn import_sound(
+        &mut self,
+        sound: usize,
+        sound_type: &SoundType,
+        path: &Path,
+        #[cfg_attr(not(feature = "wav"), allow(unused_variables))] options: &ImportOptions,
+    ) -> Result<bool> {
+        let count = self.sounds.len();
+        if sound > count {
+            return Err(Error::SoundIndexGap {
+                bank: self.index,
+                sound,
+                count,
+            });
+        }
+
+        let mut new = self
+            .sounds
+            .get(sound)
+            .cloned()
+            .unwrap_or_else(|| self.new_sound());
+        let mut writer = Cursor::new(Vec::new());
+
+        let not_mono = match sound_type {
+            SoundType::Raw => platforms::raw::import_raw(path, &mut new.entry, &mut writer),
+            #[cfg(feature = "pc")]
+            SoundType::PcWav => {
+                platforms::pc::import_wav(path, &mut new.entry, &mut writer, options)
+            }
+            #[cfg(feature = "ps2")]
+            SoundType::Ps2Vag => platforms::ps2::import_vag(path, &mut new.entry, &mut writer),
+            #[cfg(all(feature = "ps2", feature = "wav"))]
+            SoundType::Ps2Wav => {
+                platforms::ps2::import_wav(path, &mut new.entry, &mut writer, options)
+            }
+        }?;
+        new.bytes = writer.into_inner();
+
+        match self.sounds.get_mut(sound) {
+            Some(old) => *old = new,
+            None => {
+                self.push_sound(new)?;
+            }
+        }
+
+        Ok(not_mono)
+    }
+
+    /// Build a [`Bank`] from the sounds, the offset and size of the entries get updated
+    /// based on the data of the sounds.
+    pub fn to_bank(&self) -> Result<Bank> {
+        if self.sounds.len() > MAX_SOUND_ENTRIES {
+            return Err(Error::TooManySoundEntries(self.sounds.len()));
+        }
+
+        let mut bytes = Vec::with_capacity(self.sounds.iter().map(|s| s.bytes.len()).sum());
+        let sound_entries = self
+            .sounds
+            .iter()
+            .map(|sound| {
+                let entry = SoundEntry {
+                    offset: bytes.len() as u32,
+                    size: sound.bytes.len(),
+                    ..sound.entry
+                };
+                bytes.extend_from_slice(&sound.bytes);
+                entry
+            })
+            .collect();
+
+        Ok(Bank {
+            index: self.index,
+            header: BankHeader::new(sound_entries),
+            bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{rebuild_sfx, TempDir};
+
+    #[test]
+    fn remove_and_blank() {
+        let sound = |rate: u16, len: usize| EditableSound {
+            entry: SoundEntry::new(0, rate, 0),
+            bytes: vec![1; len],
+        };
+        let mut bank = EditableBank {
+            index: 3,
+            lookup_index: 0,
+            sounds: vec![sound(8000, 100), sound(11025, 200), sound(22050, 300)],
+        };
+        bank.sounds[1].entry.loop_offset = 10;
+
+        bank.blank_sound(1, Platform::Pc).unwrap();
+        assert_eq!(bank.sounds[1].bytes, BLANK_PC_SOUND);
+        assert_eq!(bank.sounds[1].entry.loop_offset, NO_LOOP);
+        assert_eq!(bank.sounds[1].entry.sample_rate, 11025);
+
+        let entries = bank.to_bank().unwrap().header.sound_entries;
+        assert_eq!(entries[1].offset, 100);
+        assert_eq!(entries[2].offset, 100 + BLANK_PC_SOUND.len() as u32);
+
+        // PS2 sounds need the end flag on their last ADPCM block
+        bank.blank_sound(1, Platform::Ps2).unwrap();
+        let blank = &bank.sounds[1].bytes;
+        assert!(blank[..16].iter().all(|&b| b == 0));
+        let flags: Vec<_> = blank[16..].chunks_exact(16).map(|block| block[1]).collect();
+        assert_eq!(flags, [1, 7]);
+        let saved = bank.to_bank().unwrap();
+        assert_eq!(
+            saved.raw_sounds().nth(1).unwrap().detect_platform(),
+            Some(Platform::Ps2)
+        );
+
+        let removed = bank.remove_sound(0).unwrap();
+        assert_eq!(removed.entry.sample_rate, 8000);
+        assert_eq!(bank.sounds[1].entry.sample_rate, 22050);
+        assert_eq!(
+            bank.to_bank().unwrap().header.sound_entries[1].offset,
+            BLANK_PS2_SOUND.len() as u32
+        );
+
+        bank.truncate(1);
+        assert_eq!(bank.sounds.len(), 1);
+        assert!(bank.remove_sound(1).is_err());
+        assert!(bank.blank_sound(1, Platform::Pc).is_err());
+    }
+
+    #[test]
+    fn add_bank() {
+        let dir = TempDir::new("sfx-add-bank");
+
+        let mut banks = 0;
+        let saved = rebuild_sfx(
+            "test-assets/PC/BankLkup.dat",
+            "test-assets/PC/FEET",
+            dir.join("FEET"),
+            |archive, output, lookup, logger| {
+                let mut archive = archive.into_editable(logger)?;
+                banks = archive.banks().len();
+                let lookup_len = lookup.len();
+
+                let bank = archive.add_bank(lookup)?;
+                assert_eq!(bank.index, banks);
+                assert_eq!(bank.lookup_index(), lookup_len);
+                let mut sound = bank.new_sound();
+                sound.bytes = vec![3; 500];
+                bank.push_sound(sound.clone())?;
+                bank.push_sound(sound)?;
+
+                archive.save(output, lookup)
+            },
+        )
+        .expect("failed to save archive");
+
+        assert_eq!(saved.banks().len(), banks + 1);
+        let bank = saved.bank(banks).unwrap();
+        assert_eq!(bank.sounds.len(), 2);
+        assert_eq!(bank.sounds[1].bytes, vec![3; 500]);
+        assert_eq!(bank.sounds[1].entry.offset, 500);
+    }
+
+    #[test]
+    fn replace_and_save() {
+        let dir = TempDir::new("sfx-editable");
+
+        let replacement = vec![7; 1234];
+        let mut edited = None;
+        let saved = rebuild_sfx(
+            "test-assets/PC/BankLkup.dat",
+            "test-assets/PC/FEET",
+            dir.join("FEET"),
+            |archive, output, lookup, logger| {
+                let mut archive = archive.into_editable(logger)?;
+                archive.replace_sound(2, 1, replacement.clone())?;
+                archive.sound_mut(2, 1).unwrap().entry.sample_rate = 11025;
+                assert!(archive.replace_sound(2, 400, Vec::new()).is_err());
+                assert!(archive.replace_sound(100, 0, Vec::new()).is_err());
+
+                archive.save(output, lookup)?;
+                edited = Some(archive);
+                Ok(())
+            },
+        )
+        .expect("failed to save archive");
+        let archive = edited.unwrap();
+
+        assert_eq!(saved.banks().len(), archive.banks().len());
+        for (saved, original) in saved.banks().iter().zip(archive.banks()) {
+            assert_eq!(saved.sounds.len(), original.sounds.len());
+            for (saved, original) in saved.sounds.iter().zip(&original.sounds) {
+                assert_eq!(saved.bytes, original.bytes);
+                assert_eq!(saved.entry.sample_rate, original.entry.sample_rate);
+            }
+        }
+
+        let sound = &saved.bank(2).unwrap().sounds[1];
+        assert_eq!(sound.bytes, replacement);
+        assert_eq!(sound.entry.sample_rate, 11025);
+    }
+}
@@ -1,30 +1,46 @@
 //! SFX archive manager.
 
+#[cfg(all(feature = "pc", feature = "ps2"))]
+use std::io::Cursor;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     ffi::OsStr,
     fs::File,
-    io::{BufReader, BufWriter, Cursor, Write},
+    io::{BufReader, BufWriter, Write},
     path::{Path, PathBuf},
 };
 
 use crate::{
     error::*,
+    config::bankslot::BankSlot,
     config::lookuptable::{LookUpEntry, LookUpTable},
     config::paknames::PakNames,
+    platform::Platform,
     reporter::{Logger, ProgressReport, ProgressReporterIterator},
     utils,
 };
 
 use bank::Banks;
+use editable::EditableBank;
 
-use self::{sound::SoundType, structures::BankHeader};
+#[cfg(feature = "wav")]
+use crate::utils::wav::Downmix;
+
+use self::{
+    sound::SoundType,
+    structures::{BankHeader, BankMetadata},
+};
 
 pub mod bank;
+pub mod editable;
 #[cfg(any(feature = "ps2", feature = "pc"))]
 mod platforms;
 pub mod sound;
-mod structures;
+pub mod structures;
+
+/// Name of the file that hold the [`BankMetadata`] inside each exported bank folder.
+#[cfg(feature = "serde")]
+const BANK_METADATA_NAME: &str = "sounds.json";
 
 type SortedLookupReturn = (Vec<(usize, LookUpEntry)>, Vec<usize>, bool);
 
@@ -35,6 +51,8 @@ type SortedLookupReturn = (Vec<(usize, LookUpEntry)>, Vec<usize>, bool);
 pub struct SfxManager {
     lookup_path: PathBuf,
     pub lookup_table: LookUpTable,
+    /// The lookup table as it was when loaded, used for updating the bank slots.
+    original_lookup: LookUpTable,
     pak_names: PakNames,
 }
 
@@ -73,6 +91,7 @@ impl SfxManager {
 
         Ok(Self {
             lookup_path: lookup_file.to_path_buf(),
+            original_lookup: lookup_table.clone(),
             lookup_table,
             pak_names,
         })
@@ -114,6 +133,62 @@ impl SfxManager {
         Ok(())
     }
 
+    /// Update the buffer sizes of the bank slots (BankSlot.dat) at `bank_slot` so they can
+    /// hold the banks after their size changed, see [`BankSlot::fit_banks`].
+    ///
+    /// `output` is optional, if `output` is `None` the original BankSlot file will be updated.
+    ///
+    /// # Note:
+    /// call this after importing into the sfx archives, otherwise the game may overflow
+    /// the slot buffers when loading the bigger banks.
+    pub fn update_bank_slot(
+        &self,
+        bank_slot: impl AsRef<Path>,
+        output: Option<PathBuf>,
+        logger: &mut impl Logger,
+    ) -> Result<()> {
+        let bank_slot = bank_slot.as_ref();
+
+        logger.info("Loading BankSlot.");
+        let mut slots = {
+            let mut reader = BufReader::new(File::open(bank_slot)?);
+            BankSlot::from_reader(&mut reader)?
+        };
+
+        let new_banks = self
+            .lookup_table
+            .len()
+            .saturating_sub(self.original_lookup.len());
+        if new_banks != 0 {
+            logger.info(format!(
+                "{new_banks} new bank(s) found, the largest slot get grown to fit them if needed."
+            ));
+        }
+
+        let changes = slots.fit_banks(&self.original_lookup, &self.lookup_table);
+        for change in &changes {
+            logger.info(format!(
+                "Slot {}: {} -> {} bytes (+{}).",
+                change.index,
+                change.old_size,
+                change.new_size,
+                change.new_size - change.old_size
+            ));
+        }
+
+        match changes.len() {
+            0 => logger.info("All banks still fit inside their slots."),
+            grown => logger.good(format!("Increased the buffer size of {grown} slot.")),
+        }
+
+        let path = output.unwrap_or_else(|| bank_slot.to_path_buf());
+        let mut writer = BufWriter::new(File::create(path)?);
+        slots.to_writer(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
     /// Try to get the sorted lookup table based on the input path basename.
     // Im almost sure there is no need for do all this, but I'll do it anyway...
     fn get_sorted_lookup_table(&self, path: &Path) -> Result<SortedLookupReturn> {
@@ -171,12 +246,42 @@ impl SfxManager {
     }
 }
 
+/// Sample rate that the imported wav files get resampled to.
+#[cfg(feature = "wav")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resample {
+    /// Keep the sample rate of the wav file, the import fail if the sample rate
+    /// doesn't fit inside the sound entry.
+    #[default]
+    Keep,
+    /// Resample to the sample rate of the sound that get replaced.
+    Original,
+    /// Resample to the given sample rate.
+    Rate(u16),
+}
+
+/// Options used when importing sounds into the banks.
+#[derive(Clone, Debug, Default)]
+pub struct ImportOptions {
+    /// Sample rate that the imported wav files get resampled to.
+    #[cfg(feature = "wav")]
+    pub resample: Resample,
+    /// How the channels of the imported wav files get mixed down to mono.
+    #[cfg(feature = "wav")]
+    pub downmix: Downmix,
+    /// Add TPDF dither to the samples of the imported wav files when they
+    /// get converted to 16-bit.
+    #[cfg(feature = "wav")]
+    pub dither: bool,
+}
+
 /// Loaded sfx archive that have the banks inside it.
 pub struct SfxArchive {
     /// Banks inside the sfx archive.
     banks: Banks,
     /// Original indexes of banks inside lookup table.
     indexes: Vec<usize>,
+    options: ImportOptions,
 }
 
 impl SfxArchive {
@@ -188,14 +293,47 @@ impl SfxArchive {
         Self {
             banks: Banks::new(reader, lookup),
             indexes,
+            options: ImportOptions::default(),
         }
     }
 
+    /// Set all the [`ImportOptions`] at once.
+    pub fn set_import_options(&mut self, options: ImportOptions) {
+        self.options = options;
+    }
+
+    /// Set the sample rate that the imported wav files get resampled to, by default
+    /// the sample rate of the files is kept as it is.
+    #[cfg(feature = "wav")]
+    pub fn set_resample(&mut self, resample: Resample) {
+        self.options.resample = resample;
+    }
+
+    /// Set how the channels of the imported wav files that aren't mono get mixed down,
+    /// the default is [`Downmix::Average`].
+    #[cfg(feature = "wav")]
+    pub fn set_downmix(&mut self, downmix: Downmix) {
+        self.options.downmix = downmix;
+    }
+
+    /// Set if TPDF dither should be added to the samples of the imported 24/32-bit and
+    /// float wav files when they get converted to 16-bit, disabled by default.
+    #[cfg(feature = "wav")]
+    pub fn set_dither(&mut self, dither: bool) {
+        self.options.dither = dither;
+    }
+
     /// get the banks inside the archive.
     pub fn banks(self) -> Banks {
         self.banks
     }
 
+    /// Guess the [`Platform`] of the archive from the data of its sounds, see
+    /// [`Banks::detect_platform`].
+    pub fn detect_platform(&mut self) -> Result<Option<Platform>> {
+        self.banks.detect_platform()
+    }
+
     /// Imports previously exported .bnk files back into a new sfx archive.
     ///
     /// # Note:
@@ -282,6 +420,15 @@ impl SfxArchive {
     /// Import sound data back to banks and then create a new sfx archive from the banks.
     ///
     /// You need to choose what kind of sound you exported previously, so program only import those types.
+    /// Sounds with an index after the last sound of the bank get added to it, as long as
+    /// there isn't any gap between them and the bank have less then 400 sounds.
+    ///
+    /// With the `serde` feature the `sounds.json` [`BankMetadata`] file of each bank folder
+    /// (that get created on export) is used for updating the loop offset, headroom and
+    /// sample rate of the sounds, the sample rate of the imported files except `Raw` ones
+    /// (and the loop of wav files that have a `smpl` chunk) take priority over it.
+    ///
+    /// Wav files get mixed down to mono and resampled based on the [`ImportOptions`] of the archive.
     pub fn import_sounds(
         self,
         sound_type: SoundType,
@@ -301,7 +448,7 @@ impl SfxArchive {
         }
 
         reporter.info("Generating file list.");
-        let folders: HashMap<usize, Vec<usize>> = folders
+        let folders: HashMap<usize, (BTreeSet<usize>, Option<BankMetadata>)> = folders
             .into_iter()
             .filter_map(|f| {
                 let (name, num) = f
@@ -315,32 +462,35 @@ impl SfxArchive {
 
                 let bank_num = num.parse::<usize>().ok()?;
 
-                let files = utils::generate_file_list(f, Some(&[sound_type.extension()]), 1);
-
-                (!files.is_empty()).then(|| {
-                    (
-                        bank_num,
-                        files
-                            .into_iter()
-                            .filter_map(|f| {
-                                let fe = f.with_extension("");
-                                let (name, num) = fe
-                                    .file_name()
-                                    .and_then(OsStr::to_str)
-                                    .and_then(|n| n.split_once('_'))?;
-
-                                if name != "sound" {
-                                    return None;
-                                }
-
-                                num.parse::<usize>().ok()
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                })
+                // metadata files can only be imported with `serde` feature
+                #[cfg(feature = "serde")]
+                let metadata = match read_bank_metadata(&f) {
+                    Ok(metadata) => metadata,
+                    Err(e) => return Some(Err(e)),
+                };
+                #[cfg(not(feature = "serde"))]
+                let metadata = None;
+
+                let files = utils::generate_file_list(&f, Some(&[sound_type.extension()]), 1)
+                    .into_iter()
+                    .filter_map(|f| {
+                        let fe = f.with_extension("");
+                        let (name, num) = fe
+                            .file_name()
+                            .and_then(OsStr::to_str)
+                            .and_then(|n| n.split_once('_'))?;
+
+                        if name != "sound" {
+                            return None;
+                        }
+
+                        num.parse::<usize>().ok()
+                    })
+                    .collect::<BTreeSet<_>>();
+
+                (!files.is_empty() || metadata.is_some()).then(|| Ok((bank_num, (files, metadata))))
             })
-            .filter(|(_, files)| !files.is_empty())
-            .collect();
+            .collect::<Result<_>>()?;
 
         reporter.good("File list generated.");
 
@@ -372,48 +522,36 @@ impl SfxArchive {
 
             entry.offset = offset;
 
-            if let Some(files) = folders.get(&bank.index) {
-                let mut soffset = 0;
-                let mut bytes_writer = Cursor::new(Vec::with_capacity(bank.bytes.len()));
-
-                for (index, sentry) in bank.header.sound_entries.iter_mut().enumerate() {
-                    sentry.offset = soffset;
-
-                    if files.contains(&index) {
-                        let path = input_path.join(format!(
-                            "bank_{:03}/sound_{index:03}.{}",
-                            bank.index,
-                            sound_type.extension()
-                        ));
-
-                        not_mono = match sound_type {
-                            SoundType::Raw => {
-                                platforms::raw::import_raw(&path, sentry, &mut bytes_writer)
-                            }
-                            #[cfg(feature = "pc")]
-                            SoundType::PcWav => {
-                                platforms::pc::import_wav(&path, sentry, &mut bytes_writer)
-                            }
-                            #[cfg(feature = "ps2")]
-                            SoundType::Ps2Vag => {
-                                platforms::ps2::import_vag(&path, sentry, &mut bytes_writer)
-                            }
-                            #[cfg(all(feature = "ps2", feature = "wav"))]
-                            SoundType::Ps2Wav => {
-                                platforms::ps2::import_wav(&path, sentry, &mut bytes_writer)
-                            }
-                        }?;
-                    } else {
-                        let offset_start = sentry.offset as usize;
-                        let offset_end = offset_start + sentry.size;
-
-                        bytes_writer.write_all(&bank.bytes[offset_start..offset_end])?;
+            if let Some((files, metadata)) = folders.get(&bank.index) {
+                let mut editable = EditableBank::from_bank(bank, index);
+                let metadata = metadata.as_ref().map_or(&[][..], |m| &m.sounds);
+
+                // the metadata get applied first, so the sample rate and loop of the
+                // imported files take priority over it
+                for (sound, metadata) in editable.sounds.iter_mut().zip(metadata) {
+                    sound.entry.apply_metadata(metadata);
+                }
+
+                // sounds that come after the last one of the bank get added to it
+                for &sound in files {
+                    if sound == editable.sounds.len() {
+                        if let Some(metadata) = metadata.get(sound) {
+                            let mut new = editable.new_sound();
+                            new.entry.apply_metadata(metadata);
+                            editable.push_sound(new)?;
+                        }
                     }
 
-                    soffset += sentry.size as u32;
+                    let path = input_path.join(format!(
+                        "bank_{:03}/sound_{sound:03}.{}",
+                        editable.index,
+                        sound_type.extension()
+                    ));
+
+                    not_mono |= editable.import_sound(sound, &sound_type, &path, &self.options)?;
                 }
 
-                bank.bytes = bytes_writer.into_inner();
+                bank = editable.to_bank()?;
             }
 
             entry.length = bank.bytes.len() as u32;
@@ -423,47 +561,111 @@ impl SfxArchive {
         }
 
         if not_mono {
-            reporter.warn("One or more of wav files wasn't mono, game may have problem in reading sfx files that have none mono audio in them.");
+            reporter.info("One or more of wav files wasn't mono, they got mixed down to mono.");
         }
 
         output_writer.flush()?;
 
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    // TODO: this module need more tests, but atm I really don't want to...
+    /// Convert all the sounds of the archive to the `target` platform and create a new
+    /// sfx archive from them, the offset and length of the banks get updated inside the
+    /// lookup table.
+    ///
+    /// The platform of the archive get detected from its sounds (see [`Banks::detect_platform`]),
+    /// PS2 sounds get decoded to 16-bit PCM for PC and PC sounds get encoded to VAG for PS2.
+    /// The sample rate, headroom and loop of the sounds are kept, but on PS2 the loop can
+    /// only start at the beginning of a vag chunk.
+    ///
+    /// # Note:
+    /// the lookup table isn't saved, you need to call [`SfxManager::update_lookup`] after this.
+    #[cfg(all(feature = "pc", feature = "ps2"))]
+    pub fn convert(
+        mut self,
+        target: Platform,
+        output: impl AsRef<Path>,
+        lookuptbl: &mut LookUpTable,
+        reporter: &mut (impl ProgressReport + Logger),
+    ) -> Result<()> {
+        let source = self.detect_platform()?.ok_or(Error::UnknownSfxPlatform)?;
+        if source == target {
+            return Err(Error::SameSfxPlatform(target));
+        }
 
-    use crate::reporter::{Logger, ProgressReport};
+        reporter.info(format!("Converting {source:?} sounds to {target:?}."));
 
-    use super::*;
+        let mut output_writer = BufWriter::with_capacity(1024 * 1024, File::create(output)?);
+        let mut offset = 0;
 
-    struct TestLogger;
+        let len = self.banks.len();
+        for (bank, index) in self.banks.banks_iter().zip(self.indexes).progress_report(
+            reporter,
+            len,
+            "Converting banks".to_owned(),
+        ) {
+            let bank = bank?;
+            let Some(entry) = lookuptbl.get_mut(index) else {
+                return Err(Error::CantFindIndexInLookUpTable);
+            };
 
-    impl Logger for TestLogger {
-        fn info(&mut self, _: impl AsRef<str>) {}
+            let wavs: Vec<_> = bank
+                .raw_sounds()
+                .map(|sound| match source {
+                    Platform::Pc => sound.as_pc_wav(),
+                    Platform::Ps2 => sound.as_ps2_wav(),
+                })
+                .collect();
+
+            let mut editable = EditableBank::from_bank(bank, index);
+            for (sound, wav) in editable.sounds.iter_mut().zip(&wavs) {
+                let mut writer = Cursor::new(Vec::new());
+                match target {
+                    Platform::Pc => platforms::pc::write_wav(wav, &mut sound.entry, &mut writer),
+                    Platform::Ps2 => platforms::ps2::write_wav(wav, &mut sound.entry, &mut writer),
+                }?;
+                sound.bytes = writer.into_inner();
+            }
 
-        fn good(&mut self, _: impl AsRef<str>) {}
+            let bank = editable.to_bank()?;
 
-        fn warn(&mut self, str: impl AsRef<str>) {
-            panic!("SFX sent a warn msg: {}", str.as_ref());
-        }
+            entry.offset = offset;
+            entry.length = bank.bytes.len() as u32;
+            offset += bank.len() as u32;
 
-        fn error(&mut self, str: impl AsRef<str>) {
-            panic!("SFX sent a error msg: {}", str.as_ref());
+            bank.to_writer(&mut output_writer)?;
         }
-    }
 
-    impl ProgressReport for TestLogger {
-        fn begin_progress(&mut self, _: String, _: usize) {}
+        output_writer.flush()?;
 
-        fn add_progress(&mut self) {}
+        reporter.good("Conversion finished and a new archive created.");
+
+        Ok(())
+    }
+}
 
-        fn end_progress(&mut self) {}
+/// Read the [`BankMetadata`] of the bank folder, if it have one.
+#[cfg(feature = "serde")]
+fn read_bank_metadata(folder: &Path) -> Result<Option<BankMetadata>> {
+    let path = folder.join(BANK_METADATA_NAME);
+    if !path.is_file() {
+        return Ok(None);
     }
 
+    let reader = BufReader::new(File::open(path)?);
+    Ok(Some(serde_json::from_reader(reader)?))
+}
+
+#[cfg(test)]
+mod test {
+    // TODO: this module need more tests, but atm I really don't want to...
+
+    use crate::test_utils::{load_sfx, rebuild_sfx, TempDir, TestLogger};
+
+    use super::*;
+    #[cfg(feature = "serde")]
+    use structures::SoundMetadata;
+
     #[test]
     fn pc() {
         let mut logger = TestLogger;
@@ -471,15 +673,361 @@ mod test {
         let sfx_manager = SfxManager::new("test-assets/PC/BankLkup.dat", None, &mut logger)
             .expect("failed to open archive");
 
-        let archive = sfx_manager
+        let mut archive = sfx_manager
             .load("test-assets/PC/FEET", &mut logger)
             .expect("failed to load archive");
 
         // check the len
         assert_eq!(archive.banks.len(), 7);
 
+        assert_eq!(archive.detect_platform().unwrap(), Some(Platform::Pc));
+
         for bank in archive.banks().banks_iter() {
-            bank.expect("Can't read the bank");
+            let bank = bank.expect("Can't read the bank");
+            assert_ne!(bank.detect_platform(), Some(Platform::Ps2));
+        }
+    }
+
+    #[test]
+    fn import_new_sounds() {
+        let dir = TempDir::new("sfx-import-new");
+        let input = dir.join("sounds");
+        std::fs::create_dir_all(input.join("bank_000")).unwrap();
+
+        let count = load_sfx("test-assets/PC/BankLkup.dat", "test-assets/PC/FEET")
+            .expect("failed to load archive")
+            .bank(0)
+            .expect("archive doesn't have the bank")
+            .sounds
+            .len();
+
+        let bank = input.join("bank_000");
+        std::fs::write(bank.join("sound_000.raw"), [1; 20]).unwrap();
+        std::fs::write(bank.join(format!("sound_{count:03}.raw")), [2; 30]).unwrap();
+        std::fs::write(bank.join(format!("sound_{:03}.raw", count + 1)), [3; 40]).unwrap();
+
+        let import = |output: &Path| {
+            rebuild_sfx(
+                "test-assets/PC/BankLkup.dat",
+                "test-assets/PC/FEET",
+                output,
+                |archive, output, lookup, logger| {
+                    archive.import_sounds(SoundType::Raw, &input, output, lookup, logger)
+                },
+            )
+        };
+
+        let archive = import(&dir.join("FEET")).expect("failed to import sounds");
+        let sounds = &archive.bank(0).unwrap().sounds;
+
+        assert_eq!(sounds.len(), count + 2);
+        assert_eq!(sounds[0].bytes, [1; 20]);
+        assert_eq!(sounds[count].bytes, [2; 30]);
+        assert_eq!(sounds[count + 1].bytes, [3; 40]);
+        assert_eq!(
+            sounds[count + 1].entry.sample_rate,
+            sounds[count - 1].entry.sample_rate
+        );
+        assert_eq!(
+            sounds[count + 1].entry.headroom,
+            sounds[count - 1].entry.headroom
+        );
+
+        // new sounds should come right after the last one
+        std::fs::write(bank.join(format!("sound_{:03}.raw", count + 5)), [4; 10]).unwrap();
+        let result = import(&dir.join("FEET.gap"));
+        assert!(matches!(result, Err(Error::SoundIndexGap { bank: 0, .. })));
+    }
+
+    #[test]
+    #[cfg(all(feature = "pc", feature = "serde"))]
+    fn sound_metadata() {
+        let mut logger = TestLogger;
+        let dir = TempDir::new("sfx-metadata");
+        let input = dir.join("sounds");
+
+        SfxManager::new("test-assets/PC/BankLkup.dat", None, &mut logger)
+            .expect("failed to open archive")
+            .load("test-assets/PC/FEET", &mut logger)
+            .expect("failed to load archive")
+            .banks()
+            .export_all_sounds(SoundType::PcWav, &input, &mut logger)
+            .expect("failed to export sounds");
+
+        let bank = input.join("bank_001");
+        let path = bank.join(BANK_METADATA_NAME);
+        let mut metadata: BankMetadata =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let original = metadata.clone();
+
+        metadata.sounds[0] = SoundMetadata {
+            loop_offset: 5,
+            sample_rate: 1234,
+            headroom: 7,
+        };
+        metadata.sounds[1].loop_offset = 9;
+        metadata.sounds[1].sample_rate = 4321;
+        std::fs::write(&path, serde_json::to_vec(&metadata).unwrap()).unwrap();
+        std::fs::remove_file(bank.join("sound_001.wav")).unwrap();
+
+        // the loop start get read from the smpl chunk of the wav
+        let mut wav = crate::utils::wav::Wav::from_file(bank.join("sound_002.wav")).unwrap();
+        wav.loop_start = Some(10);
+        wav.to_disc(bank.join("sound_002.wav")).unwrap();
+
+        let archive = rebuild_sfx(
+            "test-assets/PC/BankLkup.dat",
+            "test-assets/PC/FEET",
+            dir.join("FEET"),
+            |archive, output, lookup, logger| {
+                archive.import_sounds(SoundType::PcWav, &input, output, lookup, logger)
+            },
+        )
+        .expect("failed to import sounds");
+        let sounds = &archive.bank(1).unwrap().sounds;
+
+        assert_eq!(sounds.len(), original.sounds.len());
+        // the sample rate of the wav take priority, the loop only if it has a smpl chunk
+        assert_eq!(
+            SoundMetadata::from(&sounds[0].entry),
+            SoundMetadata {
+                sample_rate: original.sounds[0].sample_rate,
+                ..metadata.sounds[0]
+            }
+        );
+        assert_eq!(SoundMetadata::from(&sounds[1].entry), metadata.sounds[1]);
+        assert_eq!(
+            SoundMetadata::from(&sounds[2].entry),
+            SoundMetadata {
+                loop_offset: 10,
+                ..original.sounds[2]
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "pc")]
+    fn resample_import() {
+        use crate::utils::wav::Wav;
+
+        let dir = TempDir::new("sfx-resample");
+        let input = dir.join("sounds");
+        let bank = input.join("bank_000");
+        std::fs::create_dir_all(&bank).unwrap();
+
+        let original = load_sfx("test-assets/PC/BankLkup.dat", "test-assets/PC/FEET")
+            .expect("failed to load archive")
+            .bank(0)
+            .expect("archive doesn't have the bank")
+            .sounds[0]
+            .entry
+            .sample_rate;
+
+        let wav = Wav {
+            spec: hound::WavSpec {
+                channels: 1,
+                sample_rate: 96000,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+            samples: vec![0; 9600],
+            loop_start: Some(4800),
+        };
+        wav.to_disc(bank.join("sound_000.wav")).unwrap();
+
+        let import = |resample| {
+            let archive = rebuild_sfx(
+                "test-assets/PC/BankLkup.dat",
+                "test-assets/PC/FEET",
+                dir.join("FEET"),
+                |mut archive, output, lookup, logger| {
+                    archive.set_resample(resample);
+                    archive.import_sounds(SoundType::PcWav, &input, output, lookup, logger)
+                },
+            )?;
+            Result::Ok(archive.bank(0).unwrap().sounds[0].clone())
+        };
+
+        // 96kHz doesn't fit inside the sound entry
+        assert!(matches!(
+            import(Resample::Keep),
+            Err(Error::UnsupportedSampleRate(96000))
+        ));
+
+        let sound = import(Resample::Original).expect("failed to import sounds");
+        assert_eq!(sound.entry.sample_rate, original);
+        assert_eq!(sound.bytes.len(), 9600 * original as usize / 96000 * 2);
+        assert_eq!(sound.entry.loop_offset, 4800 * original as u32 / 96000);
+
+        let sound = import(Resample::Rate(24000)).expect("failed to import sounds");
+        assert_eq!(sound.entry.sample_rate, 24000);
+        assert_eq!(sound.bytes.len(), 2400 * 2);
+        assert_eq!(sound.entry.loop_offset, 1200);
+    }
+
+    #[test]
+    #[cfg(all(feature = "pc", feature = "ps2"))]
+    fn downmix_import() {
+        use crate::utils::wav::Wav;
+
+        let dir = TempDir::new("sfx-downmix");
+
+        let wav = Wav {
+            spec: hound::WavSpec {
+                channels: 2,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+            samples: [1000, -1000].repeat(280),
+            loop_start: None,
+        };
+
+        let import = |sound_type: SoundType, platform: &str, archive: &str, downmix| {
+            let input = dir.join(sound_type.extension());
+            std::fs::create_dir_all(input.join("bank_000")).unwrap();
+            wav.to_disc(input.join("bank_000/sound_000.wav")).unwrap();
+
+            let assets = Path::new("test-assets").join(platform);
+            let archive = rebuild_sfx(
+                assets.join("BankLkup.dat"),
+                assets.join(archive),
+                input.join(archive),
+                |mut archive, output, lookup, logger| {
+                    archive.set_downmix(downmix);
+                    archive.import_sounds(sound_type, &input, output, lookup, logger)
+                },
+            )?;
+            Result::Ok(archive.bank(0).unwrap().sounds[0].clone())
+        };
+
+        let sound = import(SoundType::PcWav, "PC", "FEET", Downmix::Left).unwrap();
+        assert_eq!(sound.bytes, 1000_i16.to_le_bytes().repeat(280));
+        let sound = import(SoundType::PcWav, "PC", "FEET", Downmix::Average).unwrap();
+        assert_eq!(sound.bytes, [0; 280 * 2]);
+
+        // the vag encoder only accept mono wavs
+        let sound = import(SoundType::Ps2Wav, "PS2", "FEET01.pak", Downmix::Right).unwrap();
+        assert_eq!(sound.entry.sample_rate, 22050);
+        assert!(sound.bytes.len() >= 16 + 280 / 28 * 16);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ps2", feature = "wav"))]
+    fn ps2_loop() {
+        use crate::utils::{vag::VagAudio, wav::Wav};
+
+        let mut logger = TestLogger;
+        let dir = TempDir::new("sfx-ps2-loop");
+
+        let archive = dir.join("FEET01.pak");
+        let lookup = archive.with_extension("dat");
+        let edited = rebuild_sfx(
+            "test-assets/PS2/BankLkup.dat",
+            "test-assets/PS2/FEET01.pak",
+            &archive,
+            |archive, output, lookup, logger| {
+                let mut archive = archive.into_editable(logger)?;
+                archive.sound_mut(0, 0).unwrap().entry.loop_offset = 28 * 10 + 5;
+                archive.save(output, lookup)
+            },
+        )
+        .expect("failed to save archive");
+        let original = edited.bank(0).unwrap().sounds[1].bytes.clone();
+
+        for sound_type in [SoundType::Ps2Vag, SoundType::Ps2Wav] {
+            let input = dir.join(sound_type.extension());
+            SfxManager::new(&lookup, None, &mut logger)
+                .expect("failed to open new lookup table")
+                .load(&archive, &mut logger)
+                .expect("failed to load archive")
+                .banks()
+                .export_all_sounds(sound_type, &input, &mut logger)
+                .expect("failed to export sounds");
+
+            let sounds = input.join("bank_000");
+            match sound_type {
+                SoundType::Ps2Vag => {
+                    let vag = VagAudio::from_file(sounds.join("sound_000.vag")).unwrap();
+                    assert_eq!(vag.loop_start(), Some(28 * 10));
+                    let vag = VagAudio::from_file(sounds.join("sound_001.vag")).unwrap();
+                    assert_eq!(vag.loop_start(), None);
+                }
+                _ => {
+                    let wav = Wav::from_file(sounds.join("sound_000.wav")).unwrap();
+                    assert_eq!(wav.loop_start(), Some(28 * 10 + 5));
+                }
+            }
+
+            let imported = rebuild_sfx(
+                &lookup,
+                &archive,
+                input.join("FEET01.pak"),
+                |archive, output, lookup, logger| {
+                    archive.import_sounds(sound_type, &input, output, lookup, logger)
+                },
+            )
+            .expect("failed to import sounds");
+
+            let sounds = &imported.bank(0).unwrap().sounds;
+            // the loop can only start at the beginning of a vag chunk
+            assert_eq!(sounds[0].entry.loop_offset, 28 * 10);
+            assert_eq!(sounds[1].entry.loop_offset, structures::NO_LOOP);
+            if sound_type == SoundType::Ps2Vag {
+                assert_eq!(sounds[1].bytes, original);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "pc", feature = "ps2"))]
+    fn convert() {
+        let dir = TempDir::new("sfx-convert");
+
+        let original = load_sfx("test-assets/PS2/BankLkup.dat", "test-assets/PS2/FEET01.pak")
+            .expect("failed to load archive");
+
+        let result = rebuild_sfx(
+            "test-assets/PS2/BankLkup.dat",
+            "test-assets/PS2/FEET01.pak",
+            dir.join("FEET01.pak"),
+            |archive, output, lookup, logger| {
+                archive.convert(Platform::Ps2, output, lookup, logger)
+            },
+        );
+        assert!(matches!(result, Err(Error::SameSfxPlatform(Platform::Ps2))));
+
+        // PS2 to PC and back to PS2
+        let mut input = PathBuf::from("test-assets/PS2/FEET01.pak");
+        let mut lookup = PathBuf::from("test-assets/PS2/BankLkup.dat");
+        for (target, name) in [(Platform::Pc, "FEET"), (Platform::Ps2, "FEET01.pak")] {
+            let output = dir.join(name);
+            rebuild_sfx(
+                &lookup,
+                &input,
+                &output,
+                |archive, output, lookup, logger| archive.convert(target, output, lookup, logger),
+            )
+            .expect("failed to convert the archive");
+
+            lookup = output.with_extension("dat");
+            let mut converted = SfxManager::new(&lookup, None, &mut TestLogger)
+                .unwrap()
+                .load(&output, &mut TestLogger)
+                .unwrap();
+            assert_eq!(converted.detect_platform().unwrap(), Some(target));
+            input = output;
+        }
+
+        let converted = load_sfx(lookup, input).expect("failed to load converted archive");
+        for (original, converted) in original.banks().iter().zip(converted.banks()) {
+            assert_eq!(original.sounds.len(), converted.sounds.len());
+            for (original, converted) in original.sounds.iter().zip(&converted.sounds) {
+                assert_eq!(original.entry.sample_rate, converted.entry.sample_rate);
+                assert_eq!(original.entry.headroom, converted.entry.headroom);
+                assert_eq!(original.entry.loop_offset, converted.entry.loop_offset);
+                assert_eq!(original.bytes.len(), converted.bytes.len());
+            }
         }
     }
 
@@ -490,15 +1038,18 @@ mod test {
         let sfx_manager = SfxManager::new("test-assets/PS2/BankLkup.dat", None, &mut logger)
             .expect("failed to open archive");
 
-        let archive = sfx_manager
+        let mut archive = sfx_manager
             .load("test-assets/PS2/FEET01.pak", &mut logger)
             .expect("failed to load archive");
 
         // check the len
         assert_eq!(archive.banks.len(), 7);
 
+        assert_eq!(archive.detect_platform().unwrap(), Some(Platform::Ps2));
+
         for bank in archive.banks().banks_iter() {
-            bank.expect("Can't read the bank");
+            let bank = bank.expect("Can't read the bank");
+            assert_ne!(bank.detect_platform(), Some(Platform::Pc));
         }
     }
 }
@@ -5,3 +5,55 @@ pub mod pc;
 #[cfg(feature = "ps2")]
 pub mod ps2;
 pub mod raw;
+
+#[cfg(feature = "wav")]
+use std::path::Path;
+
+#[cfg(feature = "wav")]
+use crate::{error::*, utils::wav::Wav};
+
+#[cfg(feature = "wav")]
+use super::{
+    structures::{SoundEntry, NO_LOOP},
+    ImportOptions, Resample,
+};
+
+/// Read the wav file at `path` and get it ready for importing, the wav get converted to
+/// 16-bit, mixed down to mono and then resampled based on the [`ImportOptions`], the
+/// original sample rate for resampling is taken from the sound entry.
+///
+/// Only the `smpl` chunk of the wav can change the loop, wav files without it keep the
+/// loop offset of the sound entry (from the original sound or the metadata) as long as
+/// it's inside the new sound.
+///
+/// Returns the wav and whether the file wasn't mono, or an error if the final sample
+/// rate doesn't fit inside the sound entry.
+#[cfg(feature = "wav")]
+fn read_wav(path: &Path, sentry: &SoundEntry, options: &ImportOptions) -> Result<(Wav, bool)> {
+    let mut wav = Wav::from_file_with_dither(path, options.dither)?;
+    if wav.loop_start.is_none() && sentry.loop_offset != NO_LOOP {
+        let frames = wav.samples.len() / wav.spec.channels.max(1) as usize;
+        wav.loop_start = (sentry.loop_offset < frames as u32).then_some(sentry.loop_offset);
+    }
+
+    let not_mono = wav.spec.channels != 1;
+    let wav = if not_mono {
+        wav.downmix(options.downmix)
+    } else {
+        wav
+    };
+
+    let wav = match options.resample {
+        Resample::Keep => wav,
+        Resample::Original => wav.resample(sentry.sample_rate as u32),
+        Resample::Rate(sample_rate) => wav.resample(sample_rate as u32),
+    };
+
+    match wav.spec.sample_rate {
+        0 => Err(Error::UnsupportedSampleRate(0)),
+        sample_rate if sample_rate > u16::MAX as u32 => {
+            Err(Error::UnsupportedSampleRate(sample_rate))
+        }
+        _ => Ok((wav, not_mono)),
+    }
+}
@@ -11,30 +11,45 @@ use crate::{
     error::*,
     sfx::{
         bank::Bank,
+        platforms::read_wav,
         sound::{RawSound, RawSounds},
-        structures::SoundEntry,
+        structures::{SoundEntry, NO_LOOP},
+        ImportOptions,
     },
     utils::{helpers::DataSaveAll, wav::Wav},
 };
 
 /// Imports a WAV file from the given path into the provided SoundEntry and bytes writer.
 ///
-/// Loads the WAV file, copies the sample rate and size into the SoundEntry,
-/// writes the WAV samples to the bytes writer in little endian format,
-/// and returns whether the WAV had more than 1 channel.
+/// The WAV is loaded with `read_wav` (see it for the loop and sample rate handling)
+/// and then written by `write_wav`.
+/// Returns whether the WAV file had more than 1 channel.
 pub fn import_wav(
     path: &Path,
     sentry: &mut SoundEntry,
     bytes_writer: &mut Cursor<Vec<u8>>,
+    options: &ImportOptions,
 ) -> Result<bool> {
-    let wav = Wav::from_file(path)?;
+    let (wav, not_mono) = read_wav(path, sentry, options)?;
+    write_wav(&wav, sentry, bytes_writer)?;
 
+    Ok(not_mono)
+}
+
+/// Copies the sample rate, size and loop start of the mono 16-bit `wav` into the
+/// SoundEntry and writes its samples to the bytes writer in little endian format.
+pub(crate) fn write_wav(
+    wav: &Wav,
+    sentry: &mut SoundEntry,
+    bytes_writer: &mut Cursor<Vec<u8>>,
+) -> Result<()> {
     sentry.sample_rate = wav.spec.sample_rate as _;
+    sentry.loop_offset = wav.loop_start.unwrap_or(NO_LOOP);
     sentry.size = wav.samples.len() * size_of::<i16>();
 
     wav.samples.write_le(bytes_writer)?;
 
-    Ok(wav.spec.channels != 1)
+    Ok(())
 }
 
 /// Iterator over raw sounds converted to PC WAV format.
@@ -90,8 +105,8 @@ impl<'a> RawSound<'a> {
     /// Converts the raw sound samples into a WAV format for PC.
     ///
     /// Creates a `Wav` struct with the sound converted to 16-bit PCM samples
-    /// at the source sample rate and mono channel. No validation of the raw
-    /// samples is performed.
+    /// at the source sample rate and mono channel, if the sound loop the loop
+    /// start get saved in a `smpl` chunk. No validation of the raw samples is performed.
     pub fn as_pc_wav(&self) -> Wav {
         let spec = WavSpec {
             channels: 1,
@@ -106,6 +121,10 @@ impl<'a> RawSound<'a> {
             .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
             .collect();
 
-        Wav { samples, spec }
+        Wav {
+            samples,
+            spec,
+            loop_start: self.loop_start(),
+        }
     }
 }
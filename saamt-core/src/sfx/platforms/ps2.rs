@@ -4,19 +4,21 @@ use std::{
 };
 
 #[cfg(feature = "wav")]
-use crate::utils::wav::Wav;
+use crate::utils::vag::encoder::{LoopMode, WAV2VAGEncoder};
 use crate::{
     error::*,
     sfx::{
         bank::{Bank, Banks},
         sound::{RawSound, RawSounds},
-        structures::SoundEntry,
+        structures::{SoundEntry, NO_LOOP},
     },
     utils::{
         helpers::DataSaveAll,
-        vag::{encoder::LoopMode, Vag, VagAudio},
+        vag::{Vag, VagAudio},
     },
 };
+#[cfg(feature = "wav")]
+use crate::{sfx::ImportOptions, utils::wav::Wav};
 
 #[cfg(all(target_os = "windows", feature = "ps2-export-mfaudio"))]
 use crate::reporter::Logger;
@@ -24,8 +26,9 @@ use crate::reporter::Logger;
 use crate::reporter::{ProgressReport, ProgressReporterIterator};
 
 /// Imports a VAG audio file from the given path into the provided
-/// SoundEntry and bytes writer. sets the sample rate and size on
-/// the SoundEntry, and writes the VAG raw bytes to the writer.
+/// SoundEntry and bytes writer. sets the sample rate, size and loop offset
+/// (from the loop flags of the chunks) on the SoundEntry, and writes the VAG
+/// raw bytes to the writer.
 /// Returns false to indicate the sound is mono.
 pub fn import_vag(
     path: &Path,
@@ -36,6 +39,7 @@ pub fn import_vag(
     let vag_bytes = vag.raw_vag_bytes();
 
     sentry.sample_rate = vag.0.sample_rate as _;
+    sentry.loop_offset = vag.loop_start().unwrap_or(NO_LOOP);
     sentry.size = vag_bytes.len();
 
     bytes_writer.write_all(&vag_bytes)?;
@@ -44,25 +48,46 @@ pub fn import_vag(
 }
 
 /// Imports a WAV audio file from the given path into the provided
-/// SoundEntry and bytes writer. Sets the sample rate and size on
-/// the SoundEntry, encodes the WAV to VAG format and writes the VAG
-/// raw bytes to the writer.
-/// Returns false to indicate the sound is mono.
+/// SoundEntry and bytes writer. The WAV is loaded with `read_wav` (see it for
+/// the loop and sample rate handling) and then encoded by `write_wav`.
+/// Returns whether the WAV file had more than 1 channel.
 #[cfg(feature = "wav")]
 pub fn import_wav(
     path: &Path,
     sentry: &mut SoundEntry,
     bytes_writer: &mut Cursor<Vec<u8>>,
+    options: &ImportOptions,
 ) -> Result<bool> {
-    let vag = VagAudio::from_wav(path, LoopMode::FromInput)?;
+    let (wav, not_mono) = super::read_wav(path, sentry, options)?;
+    write_wav(&wav, sentry, bytes_writer)?;
+
+    Ok(not_mono)
+}
+
+/// Encodes the mono `wav` to VAG format with the matching loop flags, sets the sample
+/// rate, size and loop offset on the SoundEntry and writes the VAG raw bytes to the writer.
+#[cfg(feature = "wav")]
+pub(crate) fn write_wav(
+    wav: &Wav,
+    sentry: &mut SoundEntry,
+    bytes_writer: &mut Cursor<Vec<u8>>,
+) -> Result<()> {
+    // the name is only stored in the vag header, which doesn't get written to the bank
+    // (`raw_vag_bytes` only keep the chunks), so the vag doesn't need one
+    let mut vag = WAV2VAGEncoder::from_wav("", wav, LoopMode::ForceNoLoop)?.generate_vag();
+    vag.set_loop(wav.loop_start);
+    // the encoder doesn't end the vag with a terminating chunk, but the game sounds have one
+    vag.add_playback_end();
     let vag_bytes = vag.raw_vag_bytes();
 
     sentry.sample_rate = vag.0.sample_rate as _;
+    // the loop can only start at the beginning of a vag chunk
+    sentry.loop_offset = vag.loop_start().unwrap_or(NO_LOOP);
     sentry.size = vag_bytes.len();
 
     bytes_writer.write_all(&vag_bytes)?;
 
-    Ok(false)
+    Ok(())
 }
 
 /// Iterator over raw sounds converted to PS2 VAG format.
@@ -234,22 +259,28 @@ impl<'a> RawSound<'a> {
     /// Converts the raw sound to a PS2 VAG audio format.
     ///
     /// This does not validate if the input is a valid VAG file.
-    /// It simply converts the raw bytes and sample rate to a VAG struct.
+    /// It simply converts the raw bytes and sample rate to a VAG struct,
+    /// and update the loop flags of the chunks to match the loop offset.
     /// The VAG struct can then be further processed and validated.
     pub fn as_ps2_vag(&self) -> VagAudio {
         let name = format!("sound_{:03}", self.index);
-        create_vag_audio(self.bytes, self.sample_rate as u32, &name)
+        let mut vag = create_vag_audio(self.bytes, self.sample_rate as u32, &name);
+        vag.set_loop(self.loop_start());
+        vag
     }
 
     /// Converts the raw PS2 sound to a WAV audio format.
     ///
     /// This converts the sound to VAG format first,
-    /// then converts the VAG data to WAV.
+    /// then converts the VAG data to WAV, the loop
+    /// start get saved in a `smpl` chunk.
     ///
     /// Requires the `wav` feature to be enabled.
     #[cfg(feature = "wav")]
     pub fn as_ps2_wav(&self) -> Wav {
-        self.as_ps2_vag().to_wav()
+        let mut wav = self.as_ps2_vag().to_wav();
+        wav.loop_start = self.loop_start();
+        wav
     }
 }
 
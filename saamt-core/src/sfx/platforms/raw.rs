@@ -11,7 +11,8 @@ use crate::{
 
 /// Imports raw PCM audio data from the given file path into the provided
 /// SoundEntry and bytes writer.
-/// We wont update sample rate, only size, user will have to take care of that.
+/// We wont update sample rate, only size, the sample rate come from the `sounds.json`
+/// metadata of the bank (if there is one) or user will have to take care of that.
 pub fn import_raw(
     path: &Path,
     sentry: &mut SoundEntry,
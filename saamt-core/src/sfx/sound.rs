@@ -2,9 +2,12 @@
 
 use std::io::Write;
 
-use super::structures::SoundEntry;
+use super::structures::{SoundEntry, NO_LOOP};
 
-use crate::error::*;
+use crate::{error::*, platform::Platform};
+
+/// Size of a PS-ADPCM block, a 2 byte header followed by 28 4-bit samples.
+const ADPCM_BLOCK_SIZE: usize = 16;
 
 /// Represents the different sound formats supported.
 ///
@@ -16,7 +19,7 @@ use crate::error::*;
 ///
 /// `Ps2Wav` is supported on PlayStation 2 builds if both `ps2` and `wav` features are enabled,
 /// for WAV audio.
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum SoundType {
     #[default]
     Raw,
@@ -29,6 +32,19 @@ pub enum SoundType {
 }
 
 impl SoundType {
+    /// get the platform that the type is for, `None` for `Raw` as it work with both of them.
+    pub fn platform(&self) -> Option<Platform> {
+        match self {
+            SoundType::Raw => None,
+            #[cfg(feature = "pc")]
+            SoundType::PcWav => Some(Platform::Pc),
+            #[cfg(feature = "ps2")]
+            SoundType::Ps2Vag => Some(Platform::Ps2),
+            #[cfg(all(feature = "ps2", feature = "wav"))]
+            SoundType::Ps2Wav => Some(Platform::Ps2),
+        }
+    }
+
     /// get the extension of the type.
     pub(crate) fn extension(&self) -> &'static str {
         match self {
@@ -57,6 +73,8 @@ pub struct RawSounds<'a> {
 pub struct RawSound<'a> {
     pub(crate) index: usize,
     pub sample_rate: u16,
+    /// Where the start of the loop is (in samples), [`NO_LOOP`] if the sound doesn't loop.
+    pub loop_offset: u32,
     pub bytes: &'a [u8],
 }
 
@@ -66,6 +84,38 @@ impl<'a> RawSound<'a> {
         writer.write_all(self.bytes)?;
         Ok(())
     }
+
+    /// Start of the loop (in samples), `None` if the sound doesn't loop.
+    pub fn loop_start(&self) -> Option<u32> {
+        (self.loop_offset != NO_LOOP).then_some(self.loop_offset)
+    }
+
+    /// Guess the platform of the sound from its data.
+    ///
+    /// PS2 sounds start with 16 zero bytes and then have PS-ADPCM blocks, each one
+    /// with a valid predictor, shift and flags in its header, anything else is
+    /// considered 16-bit PCM from PC. Returns `None` for empty and silent sounds as
+    /// they look the same on both platforms.
+    pub fn detect_platform(&self) -> Option<Platform> {
+        if self.bytes.iter().all(|&b| b == 0) {
+            return None;
+        }
+
+        let is_adpcm = self.bytes.len().is_multiple_of(ADPCM_BLOCK_SIZE)
+            && self.bytes[..ADPCM_BLOCK_SIZE].iter().all(|&b| b == 0)
+            && self.bytes[ADPCM_BLOCK_SIZE..]
+                .chunks_exact(ADPCM_BLOCK_SIZE)
+                .all(|block| {
+                    let (predictor, shift, flags) = (block[0] >> 4, block[0] & 0xF, block[1]);
+                    predictor <= 4 && shift <= 12 && flags <= 7
+                });
+
+        Some(if is_adpcm {
+            Platform::Ps2
+        } else {
+            Platform::Pc
+        })
+    }
 }
 
 impl<'a> Iterator for RawSounds<'a> {
@@ -74,6 +124,7 @@ impl<'a> Iterator for RawSounds<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         let SoundEntry {
             offset,
+            loop_offset,
             sample_rate,
             size,
             ..
@@ -87,6 +138,7 @@ impl<'a> Iterator for RawSounds<'a> {
         let raw_sound = RawSound {
             index: self.index,
             sample_rate: *sample_rate,
+            loop_offset: *loop_offset,
             bytes: &self.bytes[offset_start..offset_end],
         };
 
@@ -2,7 +2,10 @@ use std::fmt::Debug;
 
 use binrw::binrw;
 
-const MAX_SOUND_ENTRIES: usize = 400;
+/// Maximum number of sound entries inside each bank.
+pub const MAX_SOUND_ENTRIES: usize = 400;
+/// Loop offset of the sounds that doesn't loop.
+pub const NO_LOOP: u32 = 0xFFFFFFFF;
 
 /// SFX bank header
 #[binrw]
@@ -27,6 +30,14 @@ impl BankHeader {
     // 4 => num_sounds
     // 12 => size of SoundEntry
     pub const SIZE: usize = 4 + (MAX_SOUND_ENTRIES * SoundEntry::SIZE);
+
+    /// Create a new `BankHeader` from the sound entries.
+    pub fn new(sound_entries: Vec<SoundEntry>) -> Self {
+        Self {
+            padding: 0,
+            sound_entries,
+        }
+    }
 }
 
 impl Debug for BankHeader {
@@ -57,7 +68,7 @@ fn generate_sizes(mut sound_entries: Vec<SoundEntry>, len: usize) -> Vec<SoundEn
 
 /// Sound entries
 #[binrw] 
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 #[brw(little)]
 pub struct SoundEntry {
     /// Offset of the sound inside the bank.
@@ -86,10 +97,52 @@ impl SoundEntry {
     pub fn new(offset: u32, sample_rate: u16, headroom: u16) -> Self {
         Self {
             offset,
-            loop_offset: 0xFFFFFFFF,
+            loop_offset: NO_LOOP,
             sample_rate,
             headroom,
             size: 0,
         }
     }
+
+    /// Update the loop offset, sample rate and headroom of the entry based on the given [`SoundMetadata`].
+    pub fn apply_metadata(&mut self, metadata: &SoundMetadata) {
+        self.loop_offset = metadata.loop_offset;
+        self.sample_rate = metadata.sample_rate;
+        self.headroom = metadata.headroom;
+    }
+}
+
+/// Editable metadata of a sound, the same as [`SoundEntry`] but without
+/// the offset and size, as they get calculated from the sound data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SoundMetadata {
+    pub loop_offset: u32,
+    pub sample_rate: u16,
+    pub headroom: u16,
+}
+
+impl From<&SoundEntry> for SoundMetadata {
+    fn from(entry: &SoundEntry) -> Self {
+        Self {
+            loop_offset: entry.loop_offset,
+            sample_rate: entry.sample_rate,
+            headroom: entry.headroom,
+        }
+    }
+}
+
+/// Metadata of all the sounds of a bank, in the same order as the sounds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BankMetadata {
+    pub sounds: Vec<SoundMetadata>,
+}
+
+impl From<&BankHeader> for BankMetadata {
+    fn from(header: &BankHeader) -> Self {
+        Self {
+            sounds: header.sound_entries.iter().map(Into::into).collect(),
+        }
+    }
 }
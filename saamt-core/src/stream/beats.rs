@@ -0,0 +1,356 @@
+//! Beat detection for stream tracks, it can be used to generate the beat table of the
+//! dance and lowrider minigames for a replaced track.
+//!
+//! The detection is done in three steps, first an onset strength envelope get calculated
+//! from the energy changes of the audio, then the tempo get estimated using the
+//! autocorrelation of the envelope and in the end the beats get tracked using dynamic
+//! programming (based on "Beat Tracking by Dynamic Programming" by Daniel P.W. Ellis).
+
+use crate::utils::wav::Wav;
+
+use super::structures::{BeatEntry, MAX_BEAT_ENTRIES};
+
+/// Size of each analysis frame (in samples).
+const FRAME_SIZE: usize = 1024;
+/// Distance between start of each analysis frame (in samples).
+const HOP_SIZE: usize = 512;
+/// Number of frames used for removing the local average of the onset envelope.
+const AVERAGE_FRAMES: usize = 16;
+/// Number of frames on each side used for smoothing the envelope before the tempo estimation.
+const SMOOTH_FRAMES: usize = 2;
+/// Tempo that we prefer when there is more then one candidate (beats per minute).
+const PREFERRED_BPM: f32 = 120.0;
+
+/// Detect the beats of a decoded track and generate beat entries from them.
+#[derive(Clone, Debug)]
+pub struct BeatDetector {
+    /// Lowest tempo that we look for (beats per minute).
+    pub min_bpm: f32,
+    /// Highest tempo that we look for (beats per minute).
+    pub max_bpm: f32,
+    /// How strictly the beats should follow the detected tempo,
+    /// higher values mean less deviation from the tempo.
+    pub tightness: f32,
+    /// Control values of the generated beats, they get assigned to the beats in a repeating pattern.
+    pub controls: Vec<i32>,
+}
+
+impl Default for BeatDetector {
+    fn default() -> Self {
+        Self {
+            min_bpm: 60.0,
+            max_bpm: 180.0,
+            tightness: 100.0,
+            controls: vec![1, 2, 3, 4],
+        }
+    }
+}
+
+/// Result of the beat detection.
+#[derive(Clone, Debug, Default)]
+pub struct DetectedBeats {
+    /// Estimated tempo of the track (beats per minute), zero if we couldn't detect any tempo.
+    pub bpm: f32,
+    /// Generated beat entries, never more then [`MAX_BEAT_ENTRIES`].
+    pub beats: Vec<BeatEntry>,
+}
+
+impl BeatDetector {
+    /// Check that the tempo range is usable, both bounds should be finite and positive
+    /// and `min_bpm` should be lower then `max_bpm`.
+    pub fn is_valid_tempo_range(&self) -> bool {
+        self.min_bpm.is_finite()
+            && self.max_bpm.is_finite()
+            && self.min_bpm > 0.0
+            && self.min_bpm < self.max_bpm
+    }
+
+    /// Analyse the given wav and generate the beat entries for it.
+    ///
+    /// Nothing get detected if the tempo range isn't valid (see [`Self::is_valid_tempo_range`]).
+    pub fn detect(&self, wav: &Wav) -> DetectedBeats {
+        if !self.is_valid_tempo_range() {
+            return DetectedBeats::default();
+        }
+
+        let sample_rate = wav.spec.sample_rate as f32;
+        let frame_rate = sample_rate / HOP_SIZE as f32;
+
+        let envelope = onset_envelope(&mixdown(wav));
+
+        let min_lag = (60.0 * frame_rate / self.max_bpm).round().max(1.0) as usize;
+        let max_lag = (60.0 * frame_rate / self.min_bpm).round() as usize;
+        let Some(period) = estimate_period(&envelope, min_lag, max_lag, frame_rate) else {
+            return DetectedBeats::default();
+        };
+
+        let controls = if self.controls.is_empty() {
+            &[0][..]
+        } else {
+            &self.controls
+        };
+
+        let beats = track_beats(&envelope, period, self.tightness)
+            .into_iter()
+            .take(MAX_BEAT_ENTRIES)
+            .zip(controls.iter().cycle())
+            .map(|(frame, &control)| {
+                let sample = frame * HOP_SIZE + FRAME_SIZE / 2;
+                BeatEntry {
+                    timing: (sample as f32 * 1000.0 / sample_rate).round() as i32,
+                    control,
+                }
+            })
+            .collect();
+
+        DetectedBeats {
+            bpm: 60.0 * frame_rate / period,
+            beats,
+        }
+    }
+}
+
+/// Mix all the channels of the wav to a single channel.
+fn mixdown(wav: &Wav) -> Vec<f32> {
+    let channels = wav.spec.channels.max(1) as usize;
+    wav.samples
+        .chunks_exact(channels)
+        .map(|frame| {
+            frame.iter().map(|&s| s as f32).sum::<f32>() / (channels as f32 * i16::MAX as f32)
+        })
+        .collect()
+}
+
+/// Calculate the onset strength of each frame.
+///
+/// We use the energy of the first difference of the signal (which emphasize the transients)
+/// and only keep the increases of it in log scale, then remove the local average so
+/// only the peaks remain.
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+    if samples.len() < FRAME_SIZE {
+        return Vec::new();
+    }
+
+    let energies: Vec<f32> = (0..=samples.len() - FRAME_SIZE)
+        .step_by(HOP_SIZE)
+        .map(|start| {
+            let frame = &samples[start..start + FRAME_SIZE];
+            let energy: f32 = frame.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            (1.0 + energy * 1000.0).ln()
+        })
+        .collect();
+
+    let flux: Vec<f32> = std::iter::once(0.0)
+        .chain(energies.windows(2).map(|w| (w[1] - w[0]).max(0.0)))
+        .collect();
+
+    let envelope: Vec<f32> = flux
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let start = i.saturating_sub(AVERAGE_FRAMES / 2);
+            let end = (i + AVERAGE_FRAMES / 2).min(flux.len());
+            let average = flux[start..end].iter().sum::<f32>() / (end - start) as f32;
+            (v - average).max(0.0)
+        })
+        .collect();
+
+    // normalize, so the tightness work the same for loud and quiet tracks
+    let deviation = (envelope.iter().map(|v| v * v).sum::<f32>() / envelope.len() as f32).sqrt();
+    if deviation <= f32::EPSILON {
+        return envelope;
+    }
+
+    envelope.into_iter().map(|v| v / deviation).collect()
+}
+
+/// Estimate the beat period (in frames) from the autocorrelation of the onset envelope,
+/// weighted toward [`PREFERRED_BPM`].
+fn estimate_period(
+    envelope: &[f32],
+    min_lag: usize,
+    max_lag: usize,
+    frame_rate: f32,
+) -> Option<f32> {
+    if max_lag <= min_lag || envelope.len() <= max_lag.saturating_mul(2) {
+        return None;
+    }
+
+    // the period is rarely a whole number of frames, so we use a smoothed envelope,
+    // otherwise the peak get split between the neighbour lags
+    let envelope: Vec<f32> = (0..envelope.len())
+        .map(|i| {
+            let start = i.saturating_sub(SMOOTH_FRAMES);
+            let end = (i + SMOOTH_FRAMES + 1).min(envelope.len());
+            envelope[start..end]
+                .iter()
+                .enumerate()
+                .map(|(j, v)| v * (SMOOTH_FRAMES + 1 - (start + j).abs_diff(i)) as f32)
+                .sum()
+        })
+        .collect();
+
+    let preferred_lag = 60.0 * frame_rate / PREFERRED_BPM;
+    let autocorrelation = |lag: usize| -> f32 {
+        let sum: f32 = envelope
+            .iter()
+            .zip(&envelope[lag..])
+            .map(|(a, b)| a * b)
+            .sum();
+        sum / (envelope.len() - lag) as f32
+    };
+    let weighted = |lag: usize| -> f32 {
+        let octaves = (lag as f32 / preferred_lag).log2();
+        autocorrelation(lag) * (-0.5 * (octaves / 1.4).powi(2)).exp()
+    };
+
+    let scores: Vec<f32> = (min_lag - 1..=max_lag + 1).map(weighted).collect();
+    let (best, &score) = scores[1..scores.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+
+    if score <= 0.0 {
+        return None;
+    }
+
+    // parabolic interpolation between the neighbours for a better precision
+    let (prev, next) = (scores[best], scores[best + 2]);
+    let denominator = prev - 2.0 * score + next;
+    let shift = if denominator.abs() > f32::EPSILON {
+        (0.5 * (prev - next) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+
+    Some((min_lag + best) as f32 + shift)
+}
+
+/// Find the best beat positions (in frames) that both match the onsets and the period.
+fn track_beats(envelope: &[f32], period: f32, tightness: f32) -> Vec<usize> {
+    let mut score = vec![0.0_f32; envelope.len()];
+    let mut backlink: Vec<Option<usize>> = vec![None; envelope.len()];
+
+    let min_distance = (period / 2.0).round().max(1.0) as usize;
+    let max_distance = (period * 2.0).round() as usize;
+
+    for t in 0..envelope.len() {
+        let best = (t.saturating_sub(max_distance)..(t + 1).saturating_sub(min_distance))
+            .map(|prev| {
+                let cost = ((t - prev) as f32 / period).ln().powi(2);
+                (prev, score[prev] - tightness * cost)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        score[t] = envelope[t];
+        if let Some((prev, prev_score)) = best {
+            if prev_score > 0.0 {
+                score[t] += prev_score;
+                backlink[t] = Some(prev);
+            }
+        }
+    }
+
+    // start from the best scoring frame in the last period
+    let last_period = envelope.len().saturating_sub(period.round() as usize);
+    let Some(mut current) =
+        (last_period..envelope.len()).max_by(|&a, &b| score[a].total_cmp(&score[b]))
+    else {
+        return Vec::new();
+    };
+
+    let mut beats = vec![current];
+    while let Some(prev) = backlink[current] {
+        beats.push(prev);
+        current = prev;
+    }
+    beats.reverse();
+
+    beats
+}
+
+#[cfg(test)]
+mod test {
+    use hound::{SampleFormat, WavSpec};
+
+    use super::*;
+
+    const SAMPLE_RATE: usize = 22050;
+    const BEAT_MS: usize = 500; // 120 bpm
+
+    /// 20 seconds of short clicks over a quiet noise.
+    fn click_wav() -> Wav {
+        let beat_len = SAMPLE_RATE * BEAT_MS / 1000;
+        let samples: Vec<i16> = (0..SAMPLE_RATE * 20)
+            .map(|i| match i % beat_len {
+                n if n < 200 => {
+                    if n % 2 == 0 {
+                        20000
+                    } else {
+                        -20000
+                    }
+                }
+                n => ((n * 7919) % 200) as i16 - 100,
+            })
+            .collect();
+
+        Wav {
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: SAMPLE_RATE as u32,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples,
+            loop_start: None,
+        }
+    }
+
+    #[test]
+    fn click_track() {
+        let detected = BeatDetector::default().detect(&click_wav());
+
+        assert!(
+            (detected.bpm - 120.0).abs() < 2.0,
+            "bpm was {}",
+            detected.bpm
+        );
+        assert!(detected.beats.len() > 30);
+        for pair in detected.beats.windows(2) {
+            let distance = pair[1].timing - pair[0].timing;
+            assert!(
+                (distance - BEAT_MS as i32).abs() <= 30,
+                "distance was {distance}"
+            );
+        }
+        assert_eq!(detected.beats[1].control, 2);
+    }
+
+    #[test]
+    fn invalid_tempo_range() {
+        let wav = click_wav();
+        for (min_bpm, max_bpm) in [
+            (0.0, 180.0),
+            (-60.0, 180.0),
+            (f32::NAN, 180.0),
+            (60.0, f32::INFINITY),
+            (f32::MIN_POSITIVE, 180.0),
+            (120.0, 120.0),
+            (180.0, 60.0),
+        ] {
+            let detector = BeatDetector {
+                min_bpm,
+                max_bpm,
+                ..Default::default()
+            };
+            let valid = detector.is_valid_tempo_range();
+            let detected = detector.detect(&wav);
+
+            assert!(
+                valid || detected.beats.is_empty(),
+                "{min_bpm}-{max_bpm} bpm"
+            );
+            assert_eq!(valid, min_bpm == f32::MIN_POSITIVE);
+        }
+    }
+}
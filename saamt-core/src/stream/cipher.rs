@@ -0,0 +1,143 @@
+//! The XOR obfuscation used by PC stream archives.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Key used by the game to obfuscate stream archives.
+const STREAM_KEY: [u8; 16] = [
+    0xEA, 0x3A, 0xC4, 0xA1, 0x9A, 0xA8, 0x14, 0xF3, 0x48, 0xB0, 0xA7, 0x23, 0x9D, 0xE8, 0xFF, 0xF1,
+];
+
+/// Encrypt or decrypt the `buf` in place.
+///
+/// `offset` is the position of the first byte of `buf` inside the archive,
+/// the key is applied based on the position in the whole archive and not the track.
+pub(crate) fn xor_at(buf: &mut [u8], offset: u64) {
+    let start = (offset % STREAM_KEY.len() as u64) as usize;
+    buf.iter_mut()
+        .zip(STREAM_KEY.iter().cycle().skip(start))
+        .for_each(|(b, k)| *b ^= k);
+}
+
+/// A reader that decrypt a PC stream archive on the fly.
+///
+/// The position of the inner reader is used as the position inside the archive,
+/// so it should be the archive itself. seeking is supported, so only the parts
+/// that get read are decrypted.
+pub struct StreamCipherReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read + Seek> StreamCipherReader<R> {
+    /// Create a new `StreamCipherReader` starting at the current position of `inner`.
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let position = inner.stream_position()?;
+        Ok(Self { inner, position })
+    }
+}
+
+impl<R> StreamCipherReader<R> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwraps this `StreamCipherReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for StreamCipherReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        xor_at(&mut buf[..read], self.position);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for StreamCipherReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+/// A writer that encrypt the data written to a PC stream archive on the fly.
+pub struct StreamCipherWriter<W> {
+    inner: W,
+    position: u64,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> StreamCipherWriter<W> {
+    /// Create a new `StreamCipherWriter`, `offset` is the position of `inner` inside
+    /// the archive.
+    pub fn new(inner: W, offset: u64) -> Self {
+        Self {
+            inner,
+            position: offset,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwraps this `StreamCipherWriter`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for StreamCipherWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.clear();
+        self.buf.extend_from_slice(buf);
+        xor_at(&mut self.buf, self.position);
+
+        let written = self.inner.write(&self.buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for StreamCipherWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn reader_writer() {
+        let data: Vec<u8> = (0..100).collect();
+
+        let mut encrypted = data.clone();
+        xor_at(&mut encrypted, 0);
+
+        // write the second half at its offset
+        let mut writer = StreamCipherWriter::new(Vec::new(), 50);
+        writer.write_all(&data[50..]).unwrap();
+        assert_eq!(writer.into_inner(), &encrypted[50..]);
+
+        let mut reader = StreamCipherReader::new(Cursor::new(encrypted)).unwrap();
+        reader.seek(SeekFrom::Start(37)).unwrap();
+        let mut buf = [0; 20];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[37..57]);
+    }
+}
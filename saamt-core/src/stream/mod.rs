@@ -1,4 +1,1011 @@
-//! Stream manager for both PC and PS2
-//! TODO(Saeid)
+//! Stream archive manager.
 
-mod structures;
\ No newline at end of file
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::lookuptable::{LookUpEntry, LookUpTable},
+    config::paknames::PakNames,
+    error::*,
+    platform::Platform,
+    reporter::{Logger, ProgressReport, ProgressReporterIterator},
+    utils::{self, ogg::validator::OggIssue},
+};
+
+#[cfg(feature = "wav")]
+use beats::BeatDetector;
+#[cfg(all(feature = "ps2", feature = "ogg"))]
+use platforms::ps2::PS2_SAMPLE_RATE;
+use structures::TrackHeader;
+use track::{Track, TrackInfo, TrackType, Tracks};
+
+#[cfg(feature = "wav")]
+pub mod beats;
+mod cipher;
+#[cfg(any(feature = "ps2", feature = "ogg"))]
+pub mod platforms;
+pub mod station;
+pub mod structures;
+pub mod track;
+
+pub use cipher::{StreamCipherReader, StreamCipherWriter};
+
+type SortedLookupReturn = (Vec<(usize, LookUpEntry)>, Vec<usize>, bool);
+
+/// ## StreamManager
+/// StreamManager manages loading and modifying stream archives. It contains
+/// the lookup table and PAK names needed to process stream files.
+#[derive(Clone, Debug)]
+pub struct StreamManager {
+    lookup_path: PathBuf,
+    pub lookup_table: LookUpTable,
+    pak_names: PakNames,
+}
+
+impl StreamManager {
+    /// Creates a new `StreamManager` instance by loading the lookup table from the provided `lookup_file`
+    /// path (`TrakLkup.dat`) and the pak names from the optional `strmpaks_dat_file`.
+    ///
+    /// The `lookup_file` path is saved and used later when updating the lookup table.
+    ///
+    /// Logging output is written to the provided `logger`.
+    ///
+    /// Returns a `Result` with the `StreamManager` instance or a error if loading fails.
+    pub fn new<P, L>(lookup_file: P, strmpaks_dat_file: Option<P>, logger: &mut L) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        L: Logger,
+    {
+        let lookup_file = lookup_file.as_ref();
+
+        logger.info("Loading lookup table.");
+        let lookup_table = {
+            let mut reader = BufReader::new(File::open(lookup_file)?);
+            LookUpTable::from_reader(&mut reader)?
+        };
+        logger.good("Lookup table loaded.");
+
+        logger.info("Loading Pak names.");
+        let pak_names = match strmpaks_dat_file {
+            Some(path) => {
+                let mut reader = BufReader::new(File::open(path)?);
+                PakNames::stream_from_reader(&mut reader)?
+            }
+            None => PakNames::stream(), // use default stream names
+        };
+        logger.good("Pak names loaded.");
+
+        Ok(Self {
+            lookup_path: lookup_file.to_path_buf(),
+            lookup_table,
+            pak_names,
+        })
+    }
+
+    /// Load a stream archive of the given [`Platform`] and return a [`StreamArchive`].
+    pub fn load(
+        &self,
+        stream_pak: impl AsRef<Path>,
+        platform: Platform,
+        logger: &mut impl Logger,
+    ) -> Result<StreamArchive> {
+        let stream_pak = stream_pak.as_ref();
+
+        logger.info("Getting Tracks entry based on stream archive name.");
+        let (lookup, indexes, sorted) = self.get_sorted_lookup_table(stream_pak)?;
+        if sorted {
+            logger.warn("Lookup entries were not sorted, they got sorted based on their offset.");
+        }
+        logger.info("Tracks entries generated.");
+
+        logger.info("Opening stream archive.");
+        let reader = BufReader::new(File::open(stream_pak)?);
+        logger.good("Stream archive opened.");
+
+        Ok(StreamArchive::new(reader, lookup, indexes, platform))
+    }
+
+    /// Update and save the lookup table.
+    ///
+    /// `path` is optional, if `path` is `None` the original Lookup
+    /// file will be updated
+    ///
+    /// # Note:
+    /// please note that you need to call this function after loading and creating/updating new
+    /// stream files using [`StreamArchive`].
+    /// if you don't call this method the lookup file wont get updated and game wont work.
+    pub fn update_lookup(&self, path: Option<PathBuf>) -> Result<()> {
+        let path = path.unwrap_or(self.lookup_path.clone());
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.lookup_table.to_writer(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Try to get the sorted lookup table based on the input path basename.
+    fn get_sorted_lookup_table(&self, path: &Path) -> Result<SortedLookupReturn> {
+        /// Check if tracks inside the lookup are back to back based on offset.
+        fn is_tracks_sorted(lookup: &[(usize, (usize, LookUpEntry))]) -> bool {
+            lookup.windows(2).all(|e| {
+                (e[0].1 .1.offset + e[0].1 .1.length) as usize + TrackHeader::SIZE
+                    == e[1].1 .1.offset as usize
+            })
+        }
+
+        let basename = path.with_extension("");
+        let Some(basename) = basename.file_name().and_then(OsStr::to_str) else {
+            return Err(Error::CantGetBaseName(format!("{}", path.display())));
+        };
+
+        let Some(lookup_idx) = self.pak_names.get_pak_idx_from_name(basename) else {
+            return Err(Error::CantFindInLookupTable);
+        };
+
+        if self.lookup_table.count_entries_matching_pak_idx(lookup_idx) == 0 {
+            return Err(Error::NoEntryMatch);
+        }
+
+        let mut lookup: Vec<_> = self
+            .lookup_table
+            .matching_entries(lookup_idx)
+            .into_iter()
+            .enumerate()
+            .map(|(i2, (i1, e))| (i1, (i2, e)))
+            .collect();
+
+        let mut sorted = false;
+        if !is_tracks_sorted(&lookup) {
+            lookup.sort_by_key(|(_, (_, e))| e.offset);
+            sorted = true;
+            if !is_tracks_sorted(&lookup) {
+                return Err(Error::UnsortedStreamTracks);
+            }
+        }
+
+        let (indexes, lookup): (Vec<_>, Vec<_>) = lookup.into_iter().unzip();
+
+        Ok((lookup, indexes, sorted))
+    }
+}
+
+/// Loaded stream archive that have the tracks inside it.
+pub struct StreamArchive {
+    /// Tracks inside the stream archive.
+    tracks: Tracks,
+    /// Original indexes of tracks inside lookup table.
+    indexes: Vec<usize>,
+    /// Options used for importing the tracks.
+    options: ImportOptions,
+}
+
+impl StreamArchive {
+    fn new(
+        reader: BufReader<File>,
+        lookup: Vec<(usize, LookUpEntry)>,
+        indexes: Vec<usize>,
+        platform: Platform,
+    ) -> Self {
+        Self {
+            tracks: Tracks::new(reader, lookup, platform),
+            indexes,
+            options: ImportOptions::default(),
+        }
+    }
+
+    /// Set all the [`ImportOptions`] at once.
+    pub fn set_import_options(&mut self, options: ImportOptions) {
+        self.options = options;
+    }
+
+    /// Set the [`BeatDetector`] used for generating the beats of the imported tracks,
+    /// `None` (the default) keep the original beats.
+    ///
+    /// The generated beats only replace the beats of the tracks that got imported from
+    /// a decodable type (ogg tracks can only be decoded with the `ogg` feature), and
+    /// a `track_XXX.json` metadata file still take priority over them.
+    #[cfg(feature = "wav")]
+    pub fn set_beat_detector(&mut self, detector: Option<BeatDetector>) {
+        self.options.beat_detector = detector;
+    }
+
+    /// Set the vorbis VBR quality used for encoding the `PcWav` tracks, between `-0.1`
+    /// (lowest) and `1.0` (highest), the default is [`DEFAULT_OGG_QUALITY`](platforms::pc::DEFAULT_OGG_QUALITY).
+    #[cfg(feature = "ogg")]
+    pub fn set_ogg_quality(&mut self, quality: f32) {
+        self.options.ogg_quality = quality;
+    }
+
+    /// Set if the imported ogg tracks should be checked and their pages rewritten into a
+    /// layout that is safe for the game if any issue found, disabled by default.
+    ///
+    /// See [`validator::repair`](utils::ogg::validator::repair) for more info.
+    #[cfg(feature = "ogg")]
+    pub fn set_repair_ogg(&mut self, repair: bool) {
+        self.options.repair_ogg = repair;
+    }
+
+    /// get the tracks inside the archive.
+    pub fn tracks(self) -> Tracks {
+        self.tracks
+    }
+
+    /// Read the [`TrackInfo`] of all the tracks inside the archive.
+    ///
+    /// Reports progress using the given progress reporter.
+    pub fn tracks_info(self, reporter: &mut impl ProgressReport) -> Result<Vec<TrackInfo>> {
+        let platform = self.tracks.platform();
+        let len = self.tracks.len();
+
+        self.tracks
+            .tracks_iter()
+            .zip(self.indexes)
+            .progress_report(reporter, len, "Reading tracks info".to_owned())
+            .map(|(track, lookup_index)| track?.info(platform, lookup_index))
+            .collect()
+    }
+
+    /// Check the ogg data of all the tracks inside a PC archive, returns the index of the
+    /// tracks that have any issue with the issues found.
+    ///
+    /// Reports progress using the given progress reporter.
+    pub fn validate_tracks(
+        self,
+        reporter: &mut impl ProgressReport,
+    ) -> Result<Vec<(usize, Vec<OggIssue>)>> {
+        let platform = self.tracks.platform();
+        if platform != Platform::Pc {
+            return Err(Error::UnsupportedTrackType(platform));
+        }
+
+        let len = self.tracks.len();
+        let mut result = Vec::new();
+
+        for track in
+            self.tracks
+                .tracks_iter()
+                .progress_report(reporter, len, "Validating tracks".to_owned())
+        {
+            let track = track?;
+            let issues = utils::ogg::validator::validate(&track.bytes);
+            if !issues.is_empty() {
+                result.push((track.index, issues));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Imports previously exported tracks back into a new stream archive.
+    ///
+    /// You need to choose what kind of track you exported previously, so program only import those types.
+    /// Tracks that don't have a matching `track_XXX.ext` file in the `input_path` will be
+    /// copied from the original archive, the offset and length of all the tracks get updated
+    /// inside the `lookuptbl`.
+    ///
+    /// # Note:
+    /// keep in mind that the input archive that you used to load tracks in first place
+    /// shouldn't be the same as the `output`.
+    pub fn import_tracks(
+        self,
+        track_type: TrackType,
+        input_path: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        lookuptbl: &mut LookUpTable,
+        reporter: &mut (impl ProgressReport + Logger),
+    ) -> Result<()> {
+        let platform = self.tracks.platform();
+        if track_type.platform() != platform {
+            return Err(Error::UnsupportedTrackType(platform));
+        }
+
+        let input_path = input_path.as_ref();
+
+        reporter.info("Generating file list.");
+        let files = track_file_list(input_path, track_type.extension());
+        // metadata files can only be imported with `serde` feature
+        #[cfg(feature = "serde")]
+        let metadata = track_file_list(input_path, "json");
+        #[cfg(not(feature = "serde"))]
+        let metadata: HashMap<usize, PathBuf> = HashMap::new();
+        reporter.good("File list generated.");
+
+        if files.is_empty() && metadata.is_empty() {
+            return Err(Error::NoFileFound(track_type.extension()));
+        }
+
+        reporter.good(format!("Found {} track.", files.len()));
+        if !metadata.is_empty() {
+            reporter.good(format!("Found {} track metadata.", metadata.len()));
+        }
+
+        #[cfg(all(feature = "wav", not(feature = "ogg")))]
+        if self.options.beat_detector.is_some() && track_type == TrackType::Ogg {
+            reporter.warn("Beats can't be generated for ogg tracks without the `ogg` feature, original beats will be kept.");
+        }
+
+        let mut repaired = 0;
+
+        rebuild_archive(
+            self.tracks.tracks_iter().zip(self.indexes),
+            platform,
+            output.as_ref(),
+            lookuptbl,
+            reporter,
+            "Importing tracks",
+            |track| {
+                if let Some(path) = files.get(&track.index) {
+                    if self.options.import_file(track, &track_type, path, true)? {
+                        repaired += 1;
+                    }
+                }
+
+                #[cfg(feature = "serde")]
+                if let Some(path) = metadata.get(&track.index) {
+                    track.import_metadata(path)?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        if repaired != 0 {
+            reporter.good(format!("Repaired the pages of {repaired} ogg track."));
+        }
+
+        reporter.good("Import finished and a new archive created.");
+
+        Ok(())
+    }
+
+    /// Convert the archive to a new stream archive for the `target` platform.
+    ///
+    /// PC tracks get decoded from ogg vorbis and encoded to interleaved VAG, PS2 tracks
+    /// get decoded from VAG and encoded to ogg vorbis (using the quality set by
+    /// [`StreamArchive::set_ogg_quality`]). the header of the tracks (beats and lengths)
+    /// is kept as is and the offset and length of all the tracks get updated inside the
+    /// `lookuptbl`.
+    ///
+    /// PS2 tracks need to be 32000hz, so PC tracks with a different sample rate get
+    /// resampled before they are encoded.
+    #[cfg(all(feature = "ps2", feature = "ogg"))]
+    pub fn convert(
+        self,
+        target: Platform,
+        output: impl AsRef<Path>,
+        lookuptbl: &mut LookUpTable,
+        reporter: &mut (impl ProgressReport + Logger),
+    ) -> Result<()> {
+        let source = self.tracks.platform();
+        if source == target {
+            return Err(Error::SameStreamPlatform(target));
+        }
+
+        reporter.info(format!("Converting {source:?} tracks to {target:?}."));
+
+        rebuild_archive(
+            self.tracks.tracks_iter().zip(self.indexes),
+            target,
+            output.as_ref(),
+            lookuptbl,
+            reporter,
+            "Converting tracks",
+            |track| match target {
+                Platform::Pc => track.set_pc_wav(&track.as_ps2_wav()?, self.options.ogg_quality),
+                Platform::Ps2 => track.set_ps2_wav(track.as_pc_wav()?.resample(PS2_SAMPLE_RATE)),
+            },
+        )?;
+
+        reporter.good("Conversion finished and a new archive created.");
+
+        Ok(())
+    }
+}
+
+/// Options used for importing audio files into the tracks.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Detector used for generating the beats of the imported tracks, `None` keep
+    /// the original beats.
+    #[cfg(feature = "wav")]
+    pub beat_detector: Option<BeatDetector>,
+    /// Vorbis VBR quality used for encoding the `PcWav` tracks, between `-0.1` (lowest)
+    /// and `1.0` (highest).
+    #[cfg(feature = "ogg")]
+    pub ogg_quality: f32,
+    /// Rewrite the pages of the imported ogg tracks if they have any issue.
+    #[cfg(feature = "ogg")]
+    pub repair_ogg: bool,
+}
+
+// only derivable when the ogg options are disabled
+#[cfg_attr(not(feature = "ogg"), allow(clippy::derivable_impls))]
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "wav")]
+            beat_detector: None,
+            #[cfg(feature = "ogg")]
+            ogg_quality: platforms::pc::DEFAULT_OGG_QUALITY,
+            #[cfg(feature = "ogg")]
+            repair_ogg: false,
+        }
+    }
+}
+
+impl ImportOptions {
+    /// Import the audio file at `path` as the given [`TrackType`] into the track and
+    /// update its size, the beats only get generated if `detect_beats` is true.
+    ///
+    /// Returns true if the pages of the imported ogg file got repaired.
+    fn import_file(
+        &self,
+        track: &mut Track,
+        track_type: &TrackType,
+        path: &Path,
+        #[cfg_attr(
+            not(any(feature = "ogg", all(feature = "ps2", feature = "wav"))),
+            allow(unused_variables)
+        )]
+        detect_beats: bool,
+    ) -> Result<bool> {
+        #[cfg(any(feature = "ogg", all(feature = "ps2", feature = "wav")))]
+        let detector = self.beat_detector.as_ref().filter(|_| detect_beats);
+
+        let repaired = match track_type {
+            TrackType::Ogg => {
+                track.bytes = std::fs::read(path)?;
+                #[cfg(feature = "ogg")]
+                let repaired =
+                    self.repair_ogg && !utils::ogg::validator::validate(&track.bytes).is_empty();
+                #[cfg(feature = "ogg")]
+                if repaired {
+                    track.bytes = utils::ogg::validator::repair(&track.bytes)?;
+                }
+                #[cfg(feature = "ogg")]
+                if let Some(detector) = detector {
+                    track
+                        .header
+                        .set_beats(detector.detect(&track.as_pc_wav()?).beats)?;
+                }
+                #[cfg(not(feature = "ogg"))]
+                let repaired = false;
+                repaired
+            }
+            #[cfg(feature = "ogg")]
+            TrackType::PcWav => {
+                let wav = utils::wav::Wav::from_file(path)?;
+                if let Some(detector) = detector {
+                    track.header.set_beats(detector.detect(&wav).beats)?;
+                }
+                track.set_pc_wav(&wav, self.ogg_quality)?;
+                false
+            }
+            #[cfg(all(feature = "ps2", feature = "wav"))]
+            TrackType::Ps2Wav => {
+                let wav = utils::wav::Wav::from_file(path)?;
+                if let Some(detector) = detector {
+                    track.header.set_beats(detector.detect(&wav).beats)?;
+                }
+                track.set_ps2_wav(wav)?;
+                false
+            }
+        };
+        track.header.track_size = track.bytes.len() as u32;
+
+        Ok(repaired)
+    }
+}
+
+/// Write all the tracks (and their index inside the lookup table) to a new stream archive
+/// of the `platform`, every track get passed to `update` before getting written.
+/// the offset and length of the tracks get updated inside the `lookuptbl`.
+fn rebuild_archive(
+    tracks: impl ExactSizeIterator<Item = (Result<Track>, usize)>,
+    platform: Platform,
+    output: &Path,
+    lookuptbl: &mut LookUpTable,
+    reporter: &mut impl ProgressReport,
+    title: &str,
+    mut update: impl FnMut(&mut Track) -> Result<()>,
+) -> Result<()> {
+    let mut writer = BufWriter::with_capacity(1024 * 1024, File::create(output)?);
+    let mut offset = 0;
+
+    let len = tracks.len();
+    for (track, index) in tracks.progress_report(reporter, len, title.to_owned()) {
+        let mut track = track?;
+        let Some(entry) = lookuptbl.get_mut(index) else {
+            return Err(Error::CantFindIndexInLookUpTable);
+        };
+
+        update(&mut track)?;
+
+        entry.offset = offset;
+        entry.length = track.bytes.len() as u32;
+
+        match platform {
+            Platform::Pc => track.to_encrypted_writer(&mut writer, offset as u64)?,
+            Platform::Ps2 => track.to_writer(&mut writer)?,
+        }
+        offset += track.len() as u32;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Get a list of all the `track_XXX.ext` files inside the input path, mapped by their index.
+fn track_file_list(input_path: &Path, extension: &str) -> HashMap<usize, PathBuf> {
+    utils::generate_file_list(input_path, Some(&[extension]), 1)
+        .into_iter()
+        .filter_map(|f| {
+            let fe = f.with_extension("");
+            let (name, num) = fe
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(|n| n.split_once('_'))?;
+
+            if name != "track" {
+                return None;
+            }
+
+            num.parse::<usize>().map(|n| (n, f)).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use binrw::BinWrite;
+
+    use super::*;
+    use crate::stream::structures::{BeatEntry, LengthEntry, MAX_BEAT_ENTRIES};
+    use crate::stream::track::Track;
+    use crate::test_utils::{TempDir, TestLogger};
+
+    /// Create a small stream archive (named `AA`) and its lookup table
+    /// inside a temp folder, return the folder.
+    fn create_test_archive(name: &str, tracks: &[&[u8]], platform: Platform) -> TempDir {
+        let dir = TempDir::new(&format!("stream-{name}"));
+
+        let mut archive = Cursor::new(Vec::new());
+        let mut lookup = Cursor::new(Vec::new());
+
+        for (i, data) in tracks.iter().enumerate() {
+            let offset = archive.position();
+            let header = TrackHeader {
+                beats: vec![BeatEntry::default(); MAX_BEAT_ENTRIES],
+                lengths: [LengthEntry {
+                    length: i as i32,
+                    extra: 0,
+                }; 8],
+                track_size: data.len() as u32,
+            };
+
+            let mut buf = Cursor::new(Vec::new());
+            header.write(&mut buf).unwrap();
+            buf.write_all(data).unwrap();
+            let mut buf = buf.into_inner();
+            if platform == Platform::Pc {
+                cipher::xor_at(&mut buf, offset);
+            }
+            archive.write_all(&buf).unwrap();
+
+            lookup.write_all(&[0, 0, 0, 0]).unwrap();
+            lookup.write_all(&(offset as u32).to_le_bytes()).unwrap();
+            lookup
+                .write_all(&(data.len() as u32).to_le_bytes())
+                .unwrap();
+        }
+
+        std::fs::write(dir.join("AA"), archive.into_inner()).unwrap();
+        std::fs::write(dir.join("TrakLkup.dat"), lookup.into_inner()).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn pc() {
+        let mut logger = TestLogger;
+        let tracks: [&[u8]; 3] = [
+            b"OggS first track",
+            b"OggS second",
+            b"OggS third track data",
+        ];
+        let dir = create_test_archive("pc", &tracks, Platform::Pc);
+
+        let stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+
+        let archive = stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive");
+
+        assert_eq!(archive.tracks.len(), tracks.len());
+
+        for (track, data) in archive.tracks().tracks_iter().zip(tracks) {
+            let track = track.expect("Can't read the track");
+            assert_eq!(track.bytes, data);
+            assert_eq!(track.header.lengths[0].length, track.index as i32);
+            assert_eq!(track.header.track_size as usize, data.len());
+            assert!(track.header.beats.iter().all(|b| b.timing == -1));
+        }
+    }
+
+    #[test]
+    fn export_tracks() {
+        let mut logger = TestLogger;
+        let tracks: [&[u8]; 2] = [b"OggS first track", b"OggS second"];
+        let dir = create_test_archive("export", &tracks, Platform::Pc);
+
+        let stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+
+        let output_dir = dir.join("tracks");
+        stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive")
+            .tracks()
+            .export_all_tracks(TrackType::Ogg, &output_dir, &mut logger)
+            .expect("failed to export tracks");
+
+        for (index, data) in tracks.iter().enumerate() {
+            let exported = std::fs::read(output_dir.join(format!("track_{index:03}.ogg")))
+                .expect("track wasn't exported");
+            assert_eq!(&exported, data);
+        }
+    }
+
+    #[test]
+    fn import_tracks() {
+        let mut logger = TestLogger;
+        let tracks: [&[u8]; 3] = [b"OggS first track", b"OggS second", b"OggS third"];
+        let dir = create_test_archive("import", &tracks, Platform::Pc);
+
+        let input_dir = dir.join("tracks");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        let replacement = b"OggS a much longer second track than before";
+        std::fs::write(input_dir.join("track_001.ogg"), replacement).unwrap();
+
+        let mut stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+
+        let output = dir.join("output");
+        std::fs::create_dir_all(&output).unwrap();
+        stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive")
+            .import_tracks(
+                TrackType::Ogg,
+                &input_dir,
+                output.join("AA"),
+                &mut stream_manager.lookup_table,
+                &mut logger,
+            )
+            .expect("failed to import tracks");
+        stream_manager
+            .update_lookup(Some(output.join("TrakLkup.dat")))
+            .expect("failed to update lookup table");
+
+        let stream_manager = StreamManager::new(output.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open new lookup table");
+        let archive = stream_manager
+            .load(output.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load new archive");
+
+        let expected: [&[u8]; 3] = [tracks[0], replacement, tracks[2]];
+        for (track, data) in archive.tracks().tracks_iter().zip(expected) {
+            let track = track.expect("Can't read the track");
+            assert_eq!(track.bytes, data);
+            assert_eq!(track.header.track_size as usize, data.len());
+        }
+    }
+
+    #[cfg(feature = "ps2")]
+    #[test]
+    fn ps2() {
+        use crate::{stream::platforms::ps2::PS2_INTERLEAVE_SIZE, utils::vag::Vag};
+
+        let mut logger = TestLogger;
+
+        // a few valid PS-ADPCM blocks, left channel is longer then one interleave block
+        let block = |i: usize| [&[((i % 5) << 4) as u8 | 2, 0][..], &[i as u8; 14]].concat();
+        let left: Vec<u8> = (0..200).flat_map(block).collect();
+        let right: Vec<u8> = (0..100).flat_map(block).collect();
+        let vag = |data: &[u8]| {
+            Vag::new_from_raw_chunks(32000, [0; 16], data)
+                .unwrap()
+                .into()
+        };
+
+        let mut track = Track {
+            index: 0,
+            header: TrackHeader::default(),
+            bytes: Vec::new(),
+        };
+        track.set_ps2_channels(&vag(&left), &vag(&right));
+        assert_eq!(track.bytes.len() % (PS2_INTERLEAVE_SIZE * 2), 0);
+
+        let dir = create_test_archive("ps2", &[&track.bytes], Platform::Ps2);
+
+        let stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+        let track = stream_manager
+            .load(dir.join("AA"), Platform::Ps2, &mut logger)
+            .expect("failed to load archive")
+            .tracks()
+            .tracks_iter()
+            .next()
+            .expect("archive doesn't have any track")
+            .expect("Can't read the track");
+
+        let [l, r] = track
+            .ps2_channels()
+            .expect("Can't read the channels")
+            .map(|c| c.raw_chunk_bytes());
+        assert_eq!(&l[..left.len()], left);
+        assert_eq!(&r[..right.len()], right);
+        assert!(l[left.len()..]
+            .iter()
+            .chain(&r[right.len()..])
+            .all(|&b| b == 0));
+
+        // truncated and corrupted data return an error instead of panicking
+        let corrupted = [&[0, 9][..], &left[2..30]].concat();
+        assert!(Vag::new_from_raw_chunks(32000, [0; 16], &corrupted).is_err());
+        let track = Track {
+            index: 0,
+            header: TrackHeader::default(),
+            bytes: corrupted,
+        };
+        assert!(matches!(track.ps2_channels(), Err(Error::InvalidVag(_))));
+    }
+
+    #[cfg(all(feature = "ps2", feature = "ogg"))]
+    #[test]
+    fn convert_to_ps2() {
+        use crate::{stream::platforms::ps2::PS2_INTERLEAVE_SIZE, utils::wav::Wav};
+        use hound::{SampleFormat, WavSpec};
+
+        let mut logger = TestLogger;
+
+        // one second of a 440hz tone, PS2 tracks need to be 32000hz
+        let samples = (0..44100)
+            .map(|i| (i as f64 * 440.0 * std::f64::consts::TAU / 44100.0).sin())
+            .flat_map(|s| [(s * 8000.0) as i16; 2])
+            .collect();
+        let wav = Wav {
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 44100,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples,
+            loop_start: None,
+        };
+
+        let mut track = Track {
+            index: 0,
+            header: TrackHeader::default(),
+            bytes: Vec::new(),
+        };
+        track
+            .set_pc_wav(&wav, 0.5)
+            .expect("failed to encode the track");
+        // the decoded track can be a bit longer then the input
+        let frames = track.as_pc_wav().unwrap().samples.len() / 2 * 32000 / 44100;
+
+        let dir = create_test_archive("convert", &[&track.bytes], Platform::Pc);
+
+        let mut stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+        let output = dir.join("AA.new");
+        stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive")
+            .convert(
+                Platform::Ps2,
+                &output,
+                &mut stream_manager.lookup_table,
+                &mut logger,
+            )
+            .expect("failed to convert the archive");
+
+        let track = StreamArchive::new(
+            BufReader::new(File::open(&output).unwrap()),
+            stream_manager.lookup_table.matching_entries(0),
+            Vec::new(),
+            Platform::Ps2,
+        )
+        .tracks()
+        .tracks_iter()
+        .next()
+        .expect("archive doesn't have any track")
+        .expect("Can't read the track");
+
+        let wav = track.as_ps2_wav().expect("Can't decode the track");
+        assert_eq!(wav.spec.sample_rate, PS2_SAMPLE_RATE);
+        // the length include the padding of the last interleave block
+        let block_frames = PS2_INTERLEAVE_SIZE / 16 * 28;
+        let length = wav.samples.len() / 2;
+        assert!(
+            (frames..=frames + block_frames).contains(&length),
+            "track had {length} frames instead of {frames}"
+        );
+    }
+
+    #[cfg(all(feature = "ps2", feature = "wav"))]
+    #[test]
+    fn build_station() {
+        use crate::{
+            stream::station::{StationManifest, StationTrack, StationTrackKind},
+            utils::wav::Wav,
+        };
+        use hound::{SampleFormat, WavSpec};
+
+        let mut logger = TestLogger;
+        let dir = create_test_archive("station", &[b"first", b"second"], Platform::Ps2);
+
+        let wav = |seconds: usize| Wav {
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 32000,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: vec![0; 32000 * 2 * seconds],
+            loop_start: None,
+        };
+        wav(1).to_disc(dir.join("song.wav")).unwrap();
+        wav(2).to_disc(dir.join("dj.wav")).unwrap();
+
+        let track = |file: &str, kind| StationTrack {
+            file: dir.join(file),
+            kind,
+            metadata: None,
+        };
+        let mut manifest = StationManifest {
+            tracks: vec![track("song.wav", StationTrackKind::Song)],
+        };
+
+        let mut stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+        let options = ImportOptions::default();
+        let output = dir.join("AA.new");
+
+        // the manifest need one track for each lookup entry, no more and no less
+        let mut build = |manifest: &StationManifest| {
+            stream_manager.build_station(
+                "AA",
+                manifest,
+                &output,
+                Platform::Ps2,
+                &options,
+                &mut logger,
+            )
+        };
+        assert!(matches!(
+            build(&manifest),
+            Err(Error::StationTrackCount {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        let mut too_many = manifest.clone();
+        too_many.tracks.extend([
+            track("dj.wav", StationTrackKind::Dj),
+            track("song.wav", StationTrackKind::Song),
+        ]);
+        assert!(matches!(
+            build(&too_many),
+            Err(Error::StationTrackCount {
+                expected: 2,
+                found: 3,
+                ..
+            })
+        ));
+
+        manifest.tracks.push(track("dj.wav", StationTrackKind::Dj));
+        stream_manager
+            .build_station(
+                "AA",
+                &manifest,
+                &output,
+                Platform::Ps2,
+                &options,
+                &mut logger,
+            )
+            .expect("failed to build station");
+
+        let tracks: Vec<_> = StreamArchive::new(
+            BufReader::new(File::open(&output).unwrap()),
+            stream_manager.lookup_table.matching_entries(0),
+            Vec::new(),
+            Platform::Ps2,
+        )
+        .tracks()
+        .tracks_iter()
+        .collect::<Result<_>>()
+        .expect("Can't read the tracks");
+
+        assert_eq!(tracks.len(), 2);
+        for (track, ms) in tracks.iter().zip([1000, 2000]) {
+            assert_eq!(track.header.track_size as usize, track.bytes.len());
+            // the length include the padding of the last interleave block
+            let length = track.header.lengths[0].length;
+            assert!(length >= ms && length < ms + 112, "length was {length}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn metadata() {
+        use crate::stream::structures::TrackMetadata;
+
+        let mut logger = TestLogger;
+        let tracks: [&[u8]; 2] = [b"OggS first track", b"OggS second"];
+        let dir = create_test_archive("metadata", &tracks, Platform::Pc);
+
+        let mut stream_manager = StreamManager::new(dir.join("TrakLkup.dat"), None, &mut logger)
+            .expect("failed to open lookup table");
+
+        let metadata_dir = dir.join("metadata");
+        stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive")
+            .tracks()
+            .export_all_metadata(&metadata_dir, &mut logger)
+            .expect("failed to export metadata");
+
+        let path = metadata_dir.join("track_001.json");
+        let mut metadata: TrackMetadata =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(metadata.beats.is_empty());
+        assert_eq!(metadata.lengths.unwrap()[0].length, 1);
+
+        metadata.beats.push(BeatEntry {
+            timing: 1200,
+            control: 3,
+        });
+        std::fs::write(&path, serde_json::to_vec(&metadata).unwrap()).unwrap();
+
+        stream_manager
+            .load(dir.join("AA"), Platform::Pc, &mut logger)
+            .expect("failed to load archive")
+            .import_tracks(
+                TrackType::Ogg,
+                &metadata_dir,
+                dir.join("AA.new"),
+                &mut stream_manager.lookup_table,
+                &mut logger,
+            )
+            .expect("failed to import metadata");
+
+        let track = StreamArchive::new(
+            BufReader::new(File::open(dir.join("AA.new")).unwrap()),
+            stream_manager.lookup_table.matching_entries(0),
+            Vec::new(),
+            Platform::Pc,
+        )
+        .tracks()
+        .tracks_iter()
+        .nth(1)
+        .expect("archive doesn't have the track")
+        .expect("Can't read the track");
+
+        assert_eq!(track.bytes, tracks[1]);
+        assert_eq!(track.header.beats[0], metadata.beats[0]);
+        assert_eq!(track.header.beats[1], BeatEntry::default());
+    }
+}
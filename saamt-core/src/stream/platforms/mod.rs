@@ -0,0 +1,6 @@
+//! Supported platforms to work with stream tracks.
+
+#[cfg(feature = "ogg")]
+pub mod pc;
+#[cfg(feature = "ps2")]
+pub mod ps2;
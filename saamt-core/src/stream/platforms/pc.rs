@@ -0,0 +1,101 @@
+//! PC stream tracks, the audio data of each track is a ogg vorbis stream.
+
+use std::{
+    io::Cursor,
+    num::{NonZeroU32, NonZeroU8},
+    path::Path,
+};
+
+use hound::{SampleFormat, WavSpec};
+use lewton::inside_ogg::OggStreamReader;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+use crate::{error::*, stream::track::Track, utils::wav::Wav};
+
+/// Default quality used for encoding the tracks to ogg vorbis.
+pub const DEFAULT_OGG_QUALITY: f32 = 0.5;
+/// Number of samples (per channel) that get sent to the encoder at once.
+const ENCODE_BLOCK_SIZE: usize = 4096;
+
+impl Track {
+    /// Decode the ogg vorbis data of this track to a 16bit [`Wav`].
+    ///
+    /// This should only be used if you are certain that the track is from the PC version
+    /// of the game.
+    pub fn as_pc_wav(&self) -> Result<Wav> {
+        let mut reader = OggStreamReader::new(Cursor::new(&self.bytes))?;
+
+        let spec = WavSpec {
+            channels: reader.ident_hdr.audio_channels as u16,
+            sample_rate: reader.ident_hdr.audio_sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut samples = Vec::new();
+        while let Some(packet) = reader.read_dec_packet_itl()? {
+            samples.extend(packet);
+        }
+
+        Ok(Wav {
+            spec,
+            samples,
+            loop_start: None,
+        })
+    }
+
+    /// Imports a WAV audio file from the given path into this track, the WAV get
+    /// encoded to ogg vorbis using the given `quality`.
+    ///
+    /// `quality` is the vorbis VBR quality, between `-0.1` (lowest) and `1.0` (highest).
+    pub fn import_pc_wav(&mut self, path: &Path, quality: f32) -> Result<()> {
+        self.set_pc_wav(&Wav::from_file(path)?, quality)
+    }
+
+    /// Encode the given WAV audio to ogg vorbis and use it as the audio data of this
+    /// track, same as [`Track::import_pc_wav`] but for an already loaded WAV.
+    pub fn set_pc_wav(&mut self, wav: &Wav, quality: f32) -> Result<()> {
+        let (Some(sample_rate), Some(channels)) = (
+            NonZeroU32::new(wav.spec.sample_rate),
+            u8::try_from(wav.spec.channels)
+                .ok()
+                .and_then(NonZeroU8::new),
+        ) else {
+            return Err(Error::InvalidWav(format!(
+                "can't encode a wav with {} channels and {}hz sample rate",
+                wav.spec.channels, wav.spec.sample_rate
+            )));
+        };
+
+        // the encoder want planar samples
+        let mut planar = vec![
+            Vec::with_capacity(wav.samples.len() / channels.get() as usize);
+            channels.get() as usize
+        ];
+        for frame in wav.samples.chunks_exact(planar.len()) {
+            for (channel, &sample) in planar.iter_mut().zip(frame) {
+                channel.push(sample as f32 / 32768.0);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        let mut builder = VorbisEncoderBuilder::new(sample_rate, channels, &mut bytes)?;
+        builder.bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: quality,
+        });
+        let mut encoder = builder.build()?;
+
+        let len = planar.first().map_or(0, Vec::len);
+        for start in (0..len).step_by(ENCODE_BLOCK_SIZE) {
+            let end = (start + ENCODE_BLOCK_SIZE).min(len);
+            let block: Vec<_> = planar.iter().map(|c| &c[start..end]).collect();
+            encoder.encode_audio_block(block)?;
+        }
+        encoder.finish()?;
+
+        self.header.track_size = bytes.len() as u32;
+        self.bytes = bytes;
+
+        Ok(())
+    }
+}
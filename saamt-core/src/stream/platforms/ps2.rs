@@ -0,0 +1,182 @@
+//! PS2 stream tracks, PS2 archives aren't encrypted and the audio data of each
+//! track is a stereo PS-ADPCM stream, interleaved between the channels in blocks
+//! of [`PS2_INTERLEAVE_SIZE`] bytes.
+
+#[cfg(feature = "wav")]
+use std::path::Path;
+
+#[cfg(feature = "wav")]
+use hound::{SampleFormat, WavSpec};
+
+#[cfg(feature = "wav")]
+use crate::utils::{
+    vag::encoder::{LoopMode, WAV2VAGEncoder},
+    wav::Wav,
+};
+use crate::{
+    error::*,
+    stream::track::Track,
+    utils::vag::{Vag, VagAudio},
+};
+
+/// Size of each interleaved block of a channel (in bytes).
+pub const PS2_INTERLEAVE_SIZE: usize = 0x800;
+/// Sample rate of the PS2 stream tracks (measured in Hz).
+pub const PS2_SAMPLE_RATE: u32 = 32000;
+/// Size of each PS-ADPCM block.
+const ADPCM_BLOCK_SIZE: usize = 16;
+/// Number of samples inside each PS-ADPCM block.
+const ADPCM_BLOCK_SAMPLES: usize = 28;
+
+impl Track {
+    /// Returns the number of samples (per channel) of this PS2 track.
+    pub fn ps2_samples(&self) -> u64 {
+        (self.bytes.len() / 2 / ADPCM_BLOCK_SIZE * ADPCM_BLOCK_SAMPLES) as u64
+    }
+
+    /// De-interleave the PS-ADPCM data of this track into a [`VagAudio`] for each
+    /// channel (left, right).
+    ///
+    /// This should only be used if you are certain that the track is from the PS2 version
+    /// of the game, a [`Error::InvalidVag`] is returned if the data isn't valid PS-ADPCM.
+    pub fn ps2_channels(&self) -> Result<[VagAudio; 2]> {
+        let mut channels = [
+            Vec::with_capacity(self.bytes.len() / 2),
+            Vec::with_capacity(self.bytes.len() / 2),
+        ];
+
+        for (i, block) in self.bytes.chunks(PS2_INTERLEAVE_SIZE).enumerate() {
+            channels[i % 2].extend_from_slice(block);
+        }
+
+        let mut side = ["L", "R"].into_iter();
+        let [left, right] = channels.map(|mut data| {
+            // drop any incomplete block, we can't decode it anyway
+            data.truncate(data.len() - (data.len() % ADPCM_BLOCK_SIZE));
+
+            let sname = format!(
+                "track_{:03}_{}",
+                self.index,
+                side.next().unwrap_or_default()
+            );
+            let mut name = [0_u8; 16];
+            let len = sname.len().min(name.len());
+            name[..len].copy_from_slice(&sname.as_bytes()[..len]);
+
+            Vag::new_from_raw_chunks(PS2_SAMPLE_RATE, name, &data).map(VagAudio::from)
+        });
+
+        Ok([left?, right?])
+    }
+
+    /// Interleave the given channels and use them as the audio data of this track,
+    /// the shorter channel get padded with silence. The track size inside the header
+    /// also get updated.
+    pub fn set_ps2_channels(&mut self, left: &VagAudio, right: &VagAudio) {
+        let mut left = left.raw_chunk_bytes();
+        let mut right = right.raw_chunk_bytes();
+
+        let len = left
+            .len()
+            .max(right.len())
+            .next_multiple_of(PS2_INTERLEAVE_SIZE);
+        left.resize(len, 0);
+        right.resize(len, 0);
+
+        self.bytes = left
+            .chunks(PS2_INTERLEAVE_SIZE)
+            .zip(right.chunks(PS2_INTERLEAVE_SIZE))
+            .flat_map(|(l, r)| l.iter().chain(r))
+            .copied()
+            .collect();
+        self.header.track_size = self.bytes.len() as u32;
+    }
+
+    /// Decode the PS2 track to a stereo WAV audio.
+    ///
+    /// Requires the `wav` feature to be enabled.
+    #[cfg(feature = "wav")]
+    pub fn as_ps2_wav(&self) -> Result<Wav> {
+        let [left, right] = self.ps2_channels()?;
+        let left = left.decoder().to_decoded();
+        let right = right.decoder().to_decoded();
+
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: PS2_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let samples = left
+            .into_iter()
+            .zip(right)
+            .flat_map(|(l, r)| [l, r])
+            .collect();
+
+        Ok(Wav {
+            samples,
+            spec,
+            loop_start: None,
+        })
+    }
+
+    /// Imports a WAV audio file from the given path into this track. encodes each
+    /// channel of the WAV to VAG and interleave them. Mono WAV files will be used
+    /// for both of the channels.
+    ///
+    /// Requires the `wav` feature to be enabled.
+    #[cfg(feature = "wav")]
+    pub fn import_ps2_wav(&mut self, path: &Path) -> Result<()> {
+        self.set_ps2_wav(Wav::from_file(path)?)
+    }
+
+    /// Encode the given WAV audio and use it as the audio data of this track,
+    /// same as [`Track::import_ps2_wav`] but for an already loaded WAV.
+    ///
+    /// Requires the `wav` feature to be enabled.
+    #[cfg(feature = "wav")]
+    pub fn set_ps2_wav(&mut self, wav: Wav) -> Result<()> {
+        if wav.spec.sample_rate != PS2_SAMPLE_RATE {
+            return Err(Error::InvalidWav(format!(
+                "PS2 stream tracks need to be {PS2_SAMPLE_RATE}hz, but the input is {}hz",
+                wav.spec.sample_rate
+            )));
+        }
+
+        let (left, right) = match wav.spec.channels {
+            1 => (wav.samples.clone(), wav.samples),
+            2 => wav
+                .samples
+                .chunks_exact(2)
+                .map(|frame| (frame[0], frame[1]))
+                .unzip(),
+            _ => {
+                return Err(Error::InvalidWav(
+                    "PS2 stream tracks only support mono and stereo wav files".to_owned(),
+                ))
+            }
+        };
+
+        let spec = WavSpec {
+            channels: 1,
+            ..wav.spec
+        };
+        let encode = |samples: Vec<i16>, side: &str| -> Result<VagAudio> {
+            let name = format!("track_{:03}_{side}", self.index);
+            let wav = Wav {
+                spec,
+                samples,
+                loop_start: None,
+            };
+            WAV2VAGEncoder::from_wav(name, &wav, LoopMode::ForceNoLoop)
+                .map(WAV2VAGEncoder::generate_vag)
+        };
+
+        let left = encode(left, "L")?;
+        let right = encode(right, "R")?;
+        self.set_ps2_channels(&left, &right);
+
+        Ok(())
+    }
+}
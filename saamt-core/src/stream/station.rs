@@ -0,0 +1,205 @@
+//! Build a whole radio station (stream archive) from a list of audio files.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "serde")]
+use std::{fs::File, io::BufReader};
+
+use crate::{
+    error::*,
+    platform::Platform,
+    reporter::{Logger, ProgressReport},
+};
+
+use super::{
+    rebuild_archive,
+    structures::{TrackHeader, TrackMetadata},
+    track::{Track, TrackType},
+    ImportOptions, StreamManager,
+};
+
+/// Kind of a track inside a radio station.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum StationTrackKind {
+    /// A song (or a part of it), the only kind that get beats generated for it.
+    #[default]
+    Song,
+    /// A DJ line.
+    Dj,
+    /// An advert.
+    Advert,
+}
+
+/// A single track of a [`StationManifest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StationTrack {
+    /// Path of the audio file, `.ogg` or `.wav` for PC and `.wav` for PS2 stations.
+    pub file: PathBuf,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: StationTrackKind,
+    /// Beats and lengths of the track, they take priority over the generated ones.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub metadata: Option<TrackMetadata>,
+}
+
+/// Ordered list of the tracks of a radio station.
+///
+/// The game refer to the tracks by their index inside the lookup table, so the manifest
+/// should have exactly one track for every lookup entry of the station, in the same order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StationManifest {
+    pub tracks: Vec<StationTrack>,
+}
+
+impl StationManifest {
+    /// Read a json manifest from the given path, relative file paths inside the manifest
+    /// are resolved from the folder of the manifest.
+    #[cfg(feature = "serde")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let mut manifest: Self = serde_json::from_reader(reader)?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for track in &mut manifest.tracks {
+            track.file = base.join(&track.file);
+        }
+
+        Ok(manifest)
+    }
+}
+
+impl StreamManager {
+    /// Build a new stream archive for the `station` (one of the stream pak names) of the
+    /// given [`Platform`] from the tracks of the `manifest`, the offset and length of the
+    /// station entries get updated inside the lookup table.
+    ///
+    /// The type of each track is chosen based on its file extension, songs get their beats
+    /// generated if the `options` have a beat detector and the first length entry of every
+    /// track is set to its duration (in milliseconds), the metadata of the tracks take
+    /// priority over both of them.
+    ///
+    /// The manifest need one track for each lookup entry of the station, otherwise
+    /// [`Error::StationTrackCount`] is returned. the entries can't be added or removed
+    /// because the game refer to the tracks by their index inside the lookup table.
+    ///
+    /// # Note:
+    /// the lookup table isn't saved, you need to call [`StreamManager::update_lookup`] after this.
+    pub fn build_station(
+        &mut self,
+        station: &str,
+        manifest: &StationManifest,
+        output: impl AsRef<Path>,
+        platform: Platform,
+        options: &ImportOptions,
+        reporter: &mut (impl ProgressReport + Logger),
+    ) -> Result<()> {
+        let Some(pak_idx) = self.pak_names.get_pak_idx_from_name(station) else {
+            return Err(Error::CantFindInLookupTable);
+        };
+
+        let slots: Vec<_> = self
+            .lookup_table
+            .matching_entries(pak_idx)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        if slots.is_empty() {
+            return Err(Error::NoEntryMatch);
+        }
+        if slots.len() != manifest.tracks.len() {
+            return Err(Error::StationTrackCount {
+                station: station.to_owned(),
+                expected: slots.len(),
+                found: manifest.tracks.len(),
+            });
+        }
+
+        let track_types = manifest
+            .tracks
+            .iter()
+            .map(|t| station_track_type(&t.file, platform))
+            .collect::<Result<Vec<_>>>()?;
+
+        reporter.info(format!(
+            "Building {station} with {} tracks.",
+            manifest.tracks.len()
+        ));
+
+        let tracks = slots.into_iter().enumerate().map(|(index, slot)| {
+            let track = Track {
+                index,
+                header: TrackHeader::default(),
+                bytes: Vec::new(),
+            };
+            (Ok(track), slot)
+        });
+
+        let mut repaired = 0;
+        rebuild_archive(
+            tracks,
+            platform,
+            output.as_ref(),
+            &mut self.lookup_table,
+            reporter,
+            "Building station",
+            |track| {
+                let entry = &manifest.tracks[track.index];
+                if options.import_file(
+                    track,
+                    &track_types[track.index],
+                    &entry.file,
+                    entry.kind == StationTrackKind::Song,
+                )? {
+                    repaired += 1;
+                }
+
+                let duration = track.info(platform, 0)?.duration;
+                track.header.lengths[0].length = (duration * 1000.0).round() as i32;
+
+                if let Some(metadata) = &entry.metadata {
+                    track.header.apply_metadata(metadata.clone())?;
+                }
+
+                Ok(())
+            },
+        )?;
+
+        if repaired != 0 {
+            reporter.good(format!("Repaired the pages of {repaired} ogg track."));
+        }
+
+        reporter.good("Station built.");
+
+        Ok(())
+    }
+}
+
+/// Get the [`TrackType`] of a station file based on its extension.
+fn station_track_type(path: &Path, platform: Platform) -> Result<TrackType> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+
+    match (extension.as_deref(), platform) {
+        (Some("ogg"), Platform::Pc) => Ok(TrackType::Ogg),
+        #[cfg(feature = "ogg")]
+        (Some("wav"), Platform::Pc) => Ok(TrackType::PcWav),
+        #[cfg(all(feature = "ps2", feature = "wav"))]
+        (Some("wav"), Platform::Ps2) => Ok(TrackType::Ps2Wav),
+        _ => Err(Error::UnsupportedStationFile(path.display().to_string())),
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt::Debug;
+
+use binrw::binrw;
+
+use crate::error::*;
+
+/// Number of beat entries inside each track header.
+pub const MAX_BEAT_ENTRIES: usize = 1000;
+/// Number of length entries inside each track header.
+pub const MAX_LENGTH_ENTRIES: usize = 8;
+
+/// Stream track header, this header come before the audio data of every track.
+#[binrw]
+#[brw(little)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackHeader {
+    /// Beat timing and control data, used by the dance and lowrider minigames.
+    // I should just use [BeatEntry; 1000] but because serde doesn't support
+    // big arrays I'll use vector instead.
+    #[br(count = MAX_BEAT_ENTRIES)]
+    #[bw(assert(beats.len() == MAX_BEAT_ENTRIES, "beats should have exactly {MAX_BEAT_ENTRIES} entries, but instead it had {}", beats.len()))]
+    pub beats: Vec<BeatEntry>,
+    /// Length entries.
+    pub lengths: [LengthEntry; MAX_LENGTH_ENTRIES],
+    /// Size of the audio data that come after the header.
+    pub track_size: u32,
+}
+
+impl TrackHeader {
+    /// Size of header
+    // 8000 => beats
+    // 64 => lengths
+    // 4 => track_size
+    pub const SIZE: usize =
+        (MAX_BEAT_ENTRIES * BeatEntry::SIZE) + (MAX_LENGTH_ENTRIES * LengthEntry::SIZE) + 4;
+
+    /// Update the beats and lengths of the header based on the given [`TrackMetadata`].
+    ///
+    /// The lengths are only updated if the metadata have them.
+    pub fn apply_metadata(&mut self, metadata: TrackMetadata) -> Result<()> {
+        self.set_beats(metadata.beats)?;
+        if let Some(lengths) = metadata.lengths {
+            self.lengths = lengths;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the beats of the header, the unused entries get filled with the default one.
+    pub fn set_beats(&mut self, mut beats: Vec<BeatEntry>) -> Result<()> {
+        if beats.len() > MAX_BEAT_ENTRIES {
+            return Err(Error::TooManyBeatEntries(beats.len()));
+        }
+
+        beats.resize(MAX_BEAT_ENTRIES, BeatEntry::default());
+        self.beats = beats;
+
+        Ok(())
+    }
+}
+
+impl Default for TrackHeader {
+    fn default() -> Self {
+        Self {
+            beats: vec![BeatEntry::default(); MAX_BEAT_ENTRIES],
+            lengths: Default::default(),
+            track_size: 0,
+        }
+    }
+}
+
+impl Debug for TrackHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TrackHeader")
+            // .field("beats", &self.beats) // we skip this section because its too big
+            .field("lengths", &self.lengths)
+            .field("track_size", &self.track_size)
+            .finish()
+    }
+}
+
+/// Beat entry
+#[binrw]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[brw(little)]
+pub struct BeatEntry {
+    /// Time of the beat (in milliseconds), -1 if the entry isn't used.
+    pub timing: i32,
+    /// Control value of the beat, what it mean depend on the minigame.
+    pub control: i32,
+}
+
+impl BeatEntry {
+    /// Size of beat entry struct
+    pub const SIZE: usize = 8;
+}
+
+impl Default for BeatEntry {
+    fn default() -> Self {
+        Self {
+            timing: -1,
+            control: 0,
+        }
+    }
+}
+
+/// Length entry
+#[binrw]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[brw(little)]
+pub struct LengthEntry {
+    /// Length value, most of the times only the first entry is used.
+    pub length: i32,
+    /// Unknown
+    pub extra: i32,
+}
+
+impl LengthEntry {
+    /// Size of length entry struct
+    pub const SIZE: usize = 8;
+}
+
+/// Editable metadata of a track, the same as [`TrackHeader`] but without
+/// the trailing unused beats and the track size.
+///
+/// `lengths` is optional, so metadata that only contain beats (like the generated ones)
+/// keep the original lengths of the track.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackMetadata {
+    pub beats: Vec<BeatEntry>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub lengths: Option<[LengthEntry; MAX_LENGTH_ENTRIES]>,
+}
+
+impl From<&TrackHeader> for TrackMetadata {
+    fn from(header: &TrackHeader) -> Self {
+        let used = header
+            .beats
+            .iter()
+            .rposition(|b| b.timing != -1)
+            .map_or(0, |p| p + 1);
+
+        Self {
+            beats: header.beats[..used].to_vec(),
+            lengths: Some(header.lengths),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metadata_round_trip() {
+        let mut header = TrackHeader::default();
+        header.beats[0] = BeatEntry {
+            timing: 500,
+            control: 1,
+        };
+        header.beats[2] = BeatEntry {
+            timing: 1500,
+            control: 2,
+        };
+
+        let metadata = TrackMetadata::from(&header);
+        assert_eq!(metadata.beats.len(), 3);
+
+        let mut new_header = TrackHeader::default();
+        new_header
+            .apply_metadata(metadata)
+            .expect("failed to apply metadata");
+        assert_eq!(new_header.beats, header.beats);
+
+        let metadata = TrackMetadata {
+            beats: vec![BeatEntry::default(); MAX_BEAT_ENTRIES + 1],
+            lengths: None,
+        };
+        assert!(new_header.apply_metadata(metadata).is_err());
+    }
+}
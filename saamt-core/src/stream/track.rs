@@ -0,0 +1,371 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use binrw::{BinRead, BinWrite};
+
+use crate::{
+    config::lookuptable::LookUpEntry,
+    error::*,
+    platform::Platform,
+    reporter::{ProgressReport, ProgressReporterIterator},
+    utils::ogg::VorbisInfo,
+};
+
+#[cfg(feature = "ps2")]
+use super::platforms::ps2::PS2_SAMPLE_RATE;
+#[cfg(feature = "serde")]
+use super::structures::TrackMetadata;
+use super::{structures::TrackHeader, StreamCipherReader, StreamCipherWriter};
+
+/// Represents the different track formats supported.
+///
+/// The default is `Ogg`, which is the ogg vorbis data used in the PC version of the game.
+///
+/// `PcWav` is supported on PC builds if `ogg` feature is enabled, the ogg vorbis data
+/// get decoded to WAV on export and the WAV get encoded back to ogg vorbis on import.
+///
+/// `Ps2Wav` is supported on PlayStation 2 builds if both `ps2` and `wav` features are enabled,
+/// the interleaved VAG data of the track get decoded to a stereo WAV audio.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum TrackType {
+    #[default]
+    Ogg,
+    #[cfg(feature = "ogg")]
+    PcWav,
+    #[cfg(all(feature = "ps2", feature = "wav"))]
+    Ps2Wav,
+}
+
+impl TrackType {
+    /// get the extension of the type.
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            TrackType::Ogg => "ogg",
+            #[cfg(feature = "ogg")]
+            TrackType::PcWav => "wav",
+            #[cfg(all(feature = "ps2", feature = "wav"))]
+            TrackType::Ps2Wav => "wav",
+        }
+    }
+
+    /// get the platform that this type of track belong to.
+    pub fn platform(&self) -> Platform {
+        match self {
+            TrackType::Ogg => Platform::Pc,
+            #[cfg(feature = "ogg")]
+            TrackType::PcWav => Platform::Pc,
+            #[cfg(all(feature = "ps2", feature = "wav"))]
+            TrackType::Ps2Wav => Platform::Ps2,
+        }
+    }
+}
+
+/// `Tracks` struct loads tracks from a stream archive lazily.
+pub struct Tracks {
+    lookup: Vec<(usize, LookUpEntry)>,
+    lookup_idx: usize,
+    reader: BufReader<File>,
+    platform: Platform,
+}
+
+impl Tracks {
+    pub(crate) fn new(
+        reader: BufReader<File>,
+        lookup: Vec<(usize, LookUpEntry)>,
+        platform: Platform,
+    ) -> Self {
+        Self {
+            lookup,
+            lookup_idx: 0,
+            reader,
+            platform,
+        }
+    }
+
+    /// Returns an iterator over the tracks in this Tracks instance.
+    ///
+    /// This allows lazily iterating over and processing the tracks without
+    /// loading them all into memory at once.
+    pub fn tracks_iter(self) -> TracksIter {
+        TracksIter {
+            lookup: self.lookup,
+            lookup_idx: self.lookup_idx,
+            reader: self.reader,
+            platform: self.platform,
+        }
+    }
+
+    /// Returns the number of tracks in this Tracks instance.
+    pub fn len(&self) -> usize {
+        self.lookup.len()
+    }
+
+    /// Checks if there are no tracks in the reader for this Tracks instance.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the platform of the archive that the tracks belong to.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Exports all tracks to the given [`TrackType`] format from the stream archive to the
+    /// given output directory.
+    ///
+    /// Iterates over each track, exporting its audio data to a file in the output
+    /// directory named `track_XXX.ext` where `XXX` is the index of the track and `.ext`
+    /// is the extension for the given track type.
+    ///
+    /// Reports progress of the export using the given progress reporter.
+    pub fn export_all_tracks(
+        self,
+        track_type: TrackType,
+        output_dir: impl AsRef<Path>,
+        reporter: &mut impl ProgressReport,
+    ) -> Result<()> {
+        if track_type.platform() != self.platform {
+            return Err(Error::UnsupportedTrackType(self.platform));
+        }
+
+        let output_dir = output_dir.as_ref();
+
+        if !output_dir.is_dir() {
+            std::fs::create_dir_all(output_dir)?;
+        }
+
+        let tracks = self.tracks_iter();
+
+        let len = tracks.len();
+        for track in tracks.progress_report(reporter, len, "Saving tracks".to_owned()) {
+            let track = track?;
+            let mut writer = BufWriter::new(File::create(output_dir.join(format!(
+                "track_{:03}.{}",
+                track.index,
+                track_type.extension()
+            )))?);
+
+            match track_type {
+                TrackType::Ogg => track.ogg_to_writer(&mut writer),
+                #[cfg(feature = "ogg")]
+                TrackType::PcWav => track.as_pc_wav()?.to_writer(&mut writer),
+                #[cfg(all(feature = "ps2", feature = "wav"))]
+                TrackType::Ps2Wav => track.as_ps2_wav()?.to_writer(&mut writer),
+            }?;
+
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Exports the metadata (beats and lengths) of all tracks from the stream archive
+    /// to the given output directory as json.
+    ///
+    /// Only the track headers get read, the metadata of each track is saved to a file
+    /// named `track_XXX.json` where `XXX` is the index of the track. these files can be
+    /// imported back alongside the tracks.
+    ///
+    /// Reports progress of the export using the given progress reporter.
+    #[cfg(feature = "serde")]
+    pub fn export_all_metadata(
+        mut self,
+        output_dir: impl AsRef<Path>,
+        reporter: &mut impl ProgressReport,
+    ) -> Result<()> {
+        let output_dir = output_dir.as_ref();
+
+        if !output_dir.is_dir() {
+            std::fs::create_dir_all(output_dir)?;
+        }
+
+        let len = self.len();
+        for (index, entry) in self.lookup.into_iter().progress_report(
+            reporter,
+            len,
+            "Saving tracks metadata".to_owned(),
+        ) {
+            let header = read_header(&mut self.reader, entry.offset as u64, self.platform)?;
+
+            let mut writer = BufWriter::new(File::create(
+                output_dir.join(format!("track_{index:03}.json")),
+            )?);
+            serde_json::to_writer_pretty(&mut writer, &TrackMetadata::from(&header))?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read only the header of the track at `offset`.
+#[cfg(feature = "serde")]
+fn read_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    platform: Platform,
+) -> Result<TrackHeader> {
+    reader.seek(SeekFrom::Start(offset))?;
+
+    Ok(match platform {
+        Platform::Pc => TrackHeader::read(&mut StreamCipherReader::new(reader)?)?,
+        Platform::Ps2 => TrackHeader::read(reader)?,
+    })
+}
+
+/// TracksIter is an iterator that lazily iterates over the tracks in a stream
+/// archive.
+///
+/// The tracks are read (and decrypted on PC) on demand as the iterator is advanced.
+pub struct TracksIter {
+    lookup: Vec<(usize, LookUpEntry)>,
+    lookup_idx: usize,
+    reader: BufReader<File>,
+    platform: Platform,
+}
+
+impl TracksIter {
+    fn read_track(&mut self, index: usize, entry: LookUpEntry) -> Result<Track> {
+        self.reader.seek(SeekFrom::Start(entry.offset as u64))?;
+
+        let mut buf = vec![0_u8; TrackHeader::SIZE + entry.length as usize];
+        // only PC archives are encrypted
+        match self.platform {
+            Platform::Pc => StreamCipherReader::new(&mut self.reader)?.read_exact(&mut buf)?,
+            Platform::Ps2 => self.reader.read_exact(&mut buf)?,
+        }
+
+        let header = TrackHeader::read(&mut Cursor::new(&buf[..TrackHeader::SIZE]))?;
+        buf.drain(..TrackHeader::SIZE);
+
+        Ok(Track {
+            index,
+            header,
+            bytes: buf,
+        })
+    }
+}
+
+impl Iterator for TracksIter {
+    type Item = Result<Track>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, entry) = *self.lookup.get(self.lookup_idx)?;
+        self.lookup_idx += 1;
+
+        Some(self.read_track(index, entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.lookup.len() - self.lookup_idx;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for TracksIter {}
+
+/// Basic information about a track.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackInfo {
+    /// Index of the track inside the stream archive.
+    pub index: usize,
+    /// Index of the track entry inside the lookup table.
+    pub lookup_index: usize,
+    /// Size of the audio data (in bytes).
+    pub size: usize,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Duration of the track (in seconds).
+    pub duration: f64,
+}
+
+/// Represents a track inside a stream archive. Contains the track header,
+/// decrypted audio data (ogg vorbis on PC, interleaved PS-ADPCM on PS2)
+/// and index of the track.
+pub struct Track {
+    /// index of track inside the lookup index
+    pub index: usize,
+    pub header: TrackHeader,
+    pub bytes: Vec<u8>,
+}
+
+impl Track {
+    /// Write the track to the writer, the track will be written **decrypted**.
+    pub fn to_writer<W: Write + Seek>(&self, writer: &mut W) -> Result<()> {
+        self.header.write(writer)?;
+        writer.write_all(&self.bytes)?;
+        Ok(())
+    }
+
+    /// Write the track to the writer **encrypted**, `offset` is the position of the
+    /// track inside the archive.
+    pub fn to_encrypted_writer<W: Write>(&self, writer: &mut W, offset: u64) -> Result<()> {
+        let mut header = Cursor::new(Vec::with_capacity(TrackHeader::SIZE));
+        self.header.write(&mut header)?;
+
+        let mut writer = StreamCipherWriter::new(writer, offset);
+        writer.write_all(header.get_ref())?;
+        writer.write_all(&self.bytes)?;
+
+        Ok(())
+    }
+
+    /// Read a json [`TrackMetadata`] from the given path and apply it to the track header.
+    #[cfg(feature = "serde")]
+    pub fn import_metadata(&mut self, path: &Path) -> Result<()> {
+        let reader = BufReader::new(File::open(path)?);
+        let metadata: TrackMetadata = serde_json::from_reader(reader)?;
+        self.header.apply_metadata(metadata)
+    }
+
+    /// Get the [`TrackInfo`] of the track, for PC tracks the ogg vorbis identification
+    /// header get parsed.
+    ///
+    /// `platform` is the platform of the archive that the track belong to and
+    /// `lookup_index` is the index of the track entry inside the lookup table.
+    pub fn info(&self, platform: Platform, lookup_index: usize) -> Result<TrackInfo> {
+        let (sample_rate, channels, samples) = match platform {
+            Platform::Pc => {
+                let info = VorbisInfo::from_bytes(&self.bytes)?;
+                (
+                    info.ident.sample_rate,
+                    info.ident.channels as u16,
+                    info.samples,
+                )
+            }
+            #[cfg(feature = "ps2")]
+            Platform::Ps2 => (PS2_SAMPLE_RATE, 2, self.ps2_samples()),
+            #[cfg(not(feature = "ps2"))]
+            Platform::Ps2 => return Err(Error::UnsupportedTrackType(platform)),
+        };
+
+        Ok(TrackInfo {
+            index: self.index,
+            lookup_index,
+            size: self.bytes.len(),
+            sample_rate,
+            channels,
+            duration: samples as f64 / sample_rate as f64,
+        })
+    }
+
+    /// Write only the audio data (ogg vorbis) of the track to the writer.
+    pub fn ogg_to_writer(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&self.bytes)?;
+        Ok(())
+    }
+
+    /// Returns the total length of the track in bytes, including the header size.
+    pub fn len(&self) -> usize {
+        self.bytes.len() + TrackHeader::SIZE
+    }
+
+    /// is the track have any bytes in it.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
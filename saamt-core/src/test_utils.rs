@@ -0,0 +1,110 @@
+//! Helpers shared by the tests of different modules.
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    config::lookuptable::LookUpTable,
+    error::*,
+    reporter::{Logger, ProgressReport},
+    sfx::{editable::EditableSfxArchive, SfxArchive, SfxManager},
+};
+
+/// Logger that ignore the info and good messages and panic on warnings and errors.
+pub struct TestLogger;
+
+impl Logger for TestLogger {
+    fn info(&mut self, _: impl AsRef<str>) {}
+
+    fn good(&mut self, _: impl AsRef<str>) {}
+
+    fn warn(&mut self, str: impl AsRef<str>) {
+        panic!("Sent a warn msg: {}", str.as_ref());
+    }
+
+    fn error(&mut self, str: impl AsRef<str>) {
+        panic!("Sent a error msg: {}", str.as_ref());
+    }
+}
+
+impl ProgressReport for TestLogger {
+    fn begin_progress(&mut self, _: String, _: usize) {}
+
+    fn add_progress(&mut self) {}
+
+    fn end_progress(&mut self) {}
+}
+
+/// Temp folder for the files of a test, the folder get removed when this get dropped.
+///
+/// The name of the folder is unique for each call, so tests that run in parallel (or
+/// at the same time in different processes) never share their files.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create a new empty folder inside the temp folder of the system, `name` is only
+    /// used to make the folder easier to find.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "saamt-{name}-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        // a left over from a crashed run with the same process id
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("failed to create the temp folder");
+
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Open the sfx `archive` with the `lookup` table and load all of its banks.
+pub fn load_sfx(lookup: impl AsRef<Path>, archive: impl AsRef<Path>) -> Result<EditableSfxArchive> {
+    let mut logger = TestLogger;
+    SfxManager::new(lookup.as_ref(), None, &mut logger)?
+        .load(archive, &mut logger)?
+        .into_editable(&mut logger)
+}
+
+/// Open the sfx `archive` with the `lookup` table and pass it to `rebuild`, which should
+/// write a new archive to `output` and update the lookup table. The updated lookup table
+/// get saved next to `output` with `.dat` extension and the new archive get loaded back.
+pub fn rebuild_sfx<F>(
+    lookup: impl AsRef<Path>,
+    archive: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    rebuild: F,
+) -> Result<EditableSfxArchive>
+where
+    F: FnOnce(SfxArchive, &Path, &mut LookUpTable, &mut TestLogger) -> Result<()>,
+{
+    let mut logger = TestLogger;
+    let output = output.as_ref();
+    let new_lookup = output.with_extension("dat");
+
+    let mut sfx_manager = SfxManager::new(lookup.as_ref(), None, &mut logger)?;
+    let archive = sfx_manager.load(archive, &mut logger)?;
+    rebuild(archive, output, &mut sfx_manager.lookup_table, &mut logger)?;
+    sfx_manager.update_lookup(Some(new_lookup.clone()))?;
+
+    load_sfx(new_lookup, output)
+}
@@ -11,6 +11,8 @@ use walkdir::WalkDir;
 pub mod helpers;
 #[cfg(all(target_os = "windows", feature = "ps2-export-mfaudio"))]
 pub mod mfaudio;
+pub mod ogg;
+pub mod resampler;
 pub mod vag;
 #[cfg(all(feature = "wav", any(feature = "ps2", feature = "pc")))]
 pub mod wav;
@@ -0,0 +1,289 @@
+//! A minimal ogg container parser, used for inspecting the ogg vorbis data of stream tracks.
+
+use std::io::Cursor;
+
+use binrw::{binrw, BinRead, BinWrite};
+
+use crate::error::*;
+
+pub mod validator;
+
+/// Page flag, the page start with a continued packet from previous page.
+pub const PAGE_CONTINUED: u8 = 0x01;
+/// Page flag, first page of the logical stream.
+pub const PAGE_BEGIN_OF_STREAM: u8 = 0x02;
+/// Page flag, last page of the logical stream.
+pub const PAGE_END_OF_STREAM: u8 = 0x04;
+
+/// Lookup table for the CRC32 used by ogg (polynomial 0x04C11DB7, not reflected).
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Calculate the ogg CRC32 checksum of the given bytes.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, &b| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]
+    })
+}
+
+/// Ogg page header.
+#[binrw]
+#[brw(little, magic = b"OggS")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OggPageHeader {
+    /// Version of the ogg format, always 0.
+    pub version: u8,
+    /// Flags of the page (continued packet, beginning of stream, end of stream).
+    pub header_type: u8,
+    /// Position of the page in codec units, for vorbis its the number of samples (per channel)
+    /// at the end of the page, `u64::MAX` if no packet finish in this page.
+    pub granule_position: u64,
+    /// Serial number of the logical stream.
+    pub serial: u32,
+    /// Sequence number of the page inside the logical stream.
+    pub sequence: u32,
+    /// CRC32 checksum of the whole page.
+    pub checksum: u32,
+    #[br(temp)]
+    #[bw(try_calc = u8::try_from(segment_table.len())
+        .map_err(|_| "Ogg page can't have more then 255 segments"))]
+    segment_count: u8,
+    /// Size of each segment of the page data.
+    #[br(count = segment_count)]
+    pub segment_table: Vec<u8>,
+}
+
+impl OggPageHeader {
+    /// Size of the data that come after the header.
+    pub fn data_size(&self) -> usize {
+        self.segment_table.iter().map(|&s| s as usize).sum()
+    }
+}
+
+/// Ogg page, the header and its data.
+#[binrw]
+#[brw(little)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OggPage {
+    pub header: OggPageHeader,
+    #[br(count = header.data_size())]
+    pub data: Vec<u8>,
+}
+
+impl OggPage {
+    /// Calculate the checksum of the page, the checksum is calculated over the whole
+    /// page with the checksum field set to zero.
+    pub fn calculate_checksum(&self) -> Result<u32> {
+        let mut page = self.clone();
+        page.header.checksum = 0;
+
+        let mut buf = Cursor::new(Vec::with_capacity(27 + 255 + self.data.len()));
+        page.write(&mut buf)?;
+
+        Ok(crc32(buf.get_ref()))
+    }
+
+    /// Returns true if the page have the given flag.
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.header.header_type & flag != 0
+    }
+
+    /// Split the page data into the packets (or part of the packets) based on the
+    /// segment table, the bool is true if the packet is finished inside this page.
+    pub fn packets(&self) -> Vec<(&[u8], bool)> {
+        let mut packets = Vec::new();
+        let mut start = 0;
+        let mut end = 0;
+
+        for &segment in &self.header.segment_table {
+            end += segment as usize;
+            if segment < 255 {
+                packets.push((&self.data[start..end], true));
+                start = end;
+            }
+        }
+        if start != end {
+            packets.push((&self.data[start..end], false));
+        }
+
+        packets
+    }
+}
+
+/// Iterator over the pages of in memory ogg data.
+///
+/// The iterator stops after the first error.
+pub struct OggPages<'a> {
+    reader: Cursor<&'a [u8]>,
+}
+
+impl<'a> OggPages<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            reader: Cursor::new(bytes),
+        }
+    }
+
+    /// Position of the next page inside the data.
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+}
+
+impl<'a> Iterator for OggPages<'a> {
+    type Item = Result<OggPage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.reader.get_ref().len() as u64;
+        if self.reader.position() >= len {
+            return None;
+        }
+
+        let page = OggPage::read(&mut self.reader);
+        if page.is_err() {
+            self.reader.set_position(len);
+        }
+
+        Some(page.map_err(Into::into))
+    }
+}
+
+/// Vorbis identification header, the first packet of every vorbis stream.
+#[binrw]
+#[brw(little, magic = b"\x01vorbis")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VorbisIdentHeader {
+    pub version: u32,
+    pub channels: u8,
+    pub sample_rate: u32,
+    pub bitrate_maximum: i32,
+    pub bitrate_nominal: i32,
+    pub bitrate_minimum: i32,
+    pub block_sizes: u8,
+    pub framing: u8,
+}
+
+/// Basic information of an ogg vorbis stream.
+#[derive(Clone, Debug)]
+pub struct VorbisInfo {
+    pub ident: VorbisIdentHeader,
+    /// Number of samples (per channel), based on the granule position of the last page.
+    pub samples: u64,
+}
+
+impl VorbisInfo {
+    /// Parse the identification header and the last granule position of the ogg vorbis data.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut pages = OggPages::new(bytes);
+
+        let Some(first) = pages.next() else {
+            return Err(Error::InvalidOgg("no ogg page found".to_owned()));
+        };
+        let ident = VorbisIdentHeader::read(&mut Cursor::new(&first?.data))
+            .map_err(|_| Error::InvalidOgg("no vorbis identification header found".to_owned()))?;
+
+        let mut samples = 0;
+        for page in pages {
+            let page = page?;
+            if page.header.granule_position != u64::MAX {
+                samples = page.header.granule_position;
+            }
+        }
+
+        Ok(Self { ident, samples })
+    }
+
+    /// Duration of the stream in seconds.
+    pub fn duration(&self) -> f64 {
+        if self.ident.sample_rate == 0 {
+            return 0.0;
+        }
+        self.samples as f64 / self.ident.sample_rate as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vorbis_info() {
+        let page = |sequence: u32, granule_position: u64, data: Vec<u8>| OggPage {
+            header: OggPageHeader {
+                version: 0,
+                header_type: 0,
+                granule_position,
+                serial: 1,
+                sequence,
+                checksum: 0,
+                segment_table: vec![data.len() as u8],
+            },
+            data,
+        };
+
+        let mut ident = Cursor::new(Vec::new());
+        VorbisIdentHeader {
+            version: 0,
+            channels: 2,
+            sample_rate: 44100,
+            bitrate_maximum: 0,
+            bitrate_nominal: 128000,
+            bitrate_minimum: 0,
+            block_sizes: 0xB8,
+            framing: 1,
+        }
+        .write(&mut ident)
+        .unwrap();
+
+        let mut bytes = Cursor::new(Vec::new());
+        page(0, 0, ident.into_inner()).write(&mut bytes).unwrap();
+        page(1, 44100 * 2, vec![0; 10]).write(&mut bytes).unwrap();
+        page(2, u64::MAX, vec![0; 10]).write(&mut bytes).unwrap();
+
+        let info = VorbisInfo::from_bytes(bytes.get_ref()).expect("failed to parse ogg");
+        assert_eq!(info.ident.channels, 2);
+        assert_eq!(info.samples, 44100 * 2);
+        assert_eq!(info.duration(), 2.0);
+
+        assert!(VorbisInfo::from_bytes(b"not an ogg").is_err());
+    }
+
+    #[test]
+    fn segment_count() {
+        let page = |segments: usize| OggPage {
+            header: OggPageHeader {
+                version: 0,
+                header_type: 0,
+                granule_position: 0,
+                serial: 1,
+                sequence: 0,
+                checksum: 0,
+                segment_table: vec![1; segments],
+            },
+            data: vec![0; segments],
+        };
+
+        assert!(page(255).write(&mut Cursor::new(Vec::new())).is_ok());
+        // the segment count is a single byte, it shouldn't silently wrap around
+        assert!(page(256).write(&mut Cursor::new(Vec::new())).is_err());
+        assert!(page(256).calculate_checksum().is_err());
+    }
+}
@@ -0,0 +1,503 @@
+//! Validation and repair of the ogg vorbis data of the PC stream tracks.
+//!
+//! The game is a lot less forgiving than most players, a track that play fine
+//! on the PC can still stutter, loop wrong or crash the game if its pages are broken.
+
+use std::fmt;
+
+use super::*;
+
+/// Size of the data of each audio page written by [`repair`].
+#[cfg(feature = "ogg")]
+const TARGET_PAGE_SIZE: usize = 4096;
+
+/// Signatures of the three vorbis header packets, in order.
+const VORBIS_HEADERS: [&[u8]; 3] = [b"\x01vorbis", b"\x03vorbis", b"\x05vorbis"];
+
+/// A problem found inside ogg vorbis data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OggIssue {
+    /// The data at the given offset isn't a valid ogg page, nothing after it got checked.
+    InvalidPage(u64),
+    /// The checksum of the page is wrong.
+    BadChecksum(usize),
+    /// The page belong to another logical stream, the game only play the first one.
+    SerialMismatch(usize),
+    /// The sequence number of the page isn't the next one.
+    SequenceGap {
+        page: usize,
+        expected: u32,
+        found: u32,
+    },
+    /// The granule position of the page is lower than the previous pages.
+    GranuleNotMonotonic(usize),
+    /// The continued flag of the page doesn't match the end of the previous page.
+    BrokenContinuation(usize),
+    /// The first page isn't marked as the beginning of the stream.
+    MissingBeginOfStream,
+    /// The last page isn't marked as the end of the stream.
+    MissingEndOfStream,
+    /// The stream doesn't start with the three vorbis headers.
+    MissingVorbisHeaders,
+    /// The identification header isn't alone on the first page, or the audio doesn't
+    /// start on a new page after the setup header.
+    HeadersNotOnOwnPages,
+}
+
+impl fmt::Display for OggIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OggIssue::InvalidPage(offset) => write!(f, "invalid ogg page at offset {offset}"),
+            OggIssue::BadChecksum(page) => write!(f, "page {page} has a bad checksum"),
+            OggIssue::SerialMismatch(page) => {
+                write!(f, "page {page} belongs to another logical stream")
+            }
+            OggIssue::SequenceGap {
+                page,
+                expected,
+                found,
+            } => write!(
+                f,
+                "page {page} has sequence number {found}, expected {expected}"
+            ),
+            OggIssue::GranuleNotMonotonic(page) => {
+                write!(f, "page {page} granule position goes backward")
+            }
+            OggIssue::BrokenContinuation(page) => {
+                write!(
+                    f,
+                    "page {page} continued flag doesn't match the previous page"
+                )
+            }
+            OggIssue::MissingBeginOfStream => {
+                write!(f, "first page isn't marked as begin of stream")
+            }
+            OggIssue::MissingEndOfStream => write!(f, "last page isn't marked as end of stream"),
+            OggIssue::MissingVorbisHeaders => {
+                write!(f, "vorbis headers are missing or out of order")
+            }
+            OggIssue::HeadersNotOnOwnPages => {
+                write!(f, "vorbis headers aren't on their own pages")
+            }
+        }
+    }
+}
+
+/// A packet of the first logical stream and the pages it start and end on.
+struct Packet {
+    data: Vec<u8>,
+    first_page: usize,
+    last_page: usize,
+}
+
+/// Pages and packets of the first logical stream of ogg data.
+struct Demuxed {
+    pages: Vec<OggPage>,
+    packets: Vec<Packet>,
+    issues: Vec<OggIssue>,
+}
+
+/// Read the pages of the ogg data and split the first logical stream into packets,
+/// every problem found on the way get collected.
+fn demux(bytes: &[u8]) -> Demuxed {
+    let mut pages = Vec::new();
+    let mut packets = Vec::new();
+    let mut issues = Vec::new();
+
+    let mut iter = OggPages::new(bytes);
+    let mut serial = None;
+    let mut partial: Option<Packet> = None;
+    let mut last_granule = 0;
+
+    for index in 0.. {
+        let offset = iter.position();
+        let page = match iter.next() {
+            Some(Ok(page)) => page,
+            Some(Err(_)) => {
+                issues.push(OggIssue::InvalidPage(offset));
+                break;
+            }
+            None => break,
+        };
+
+        if page.calculate_checksum().ok() != Some(page.header.checksum) {
+            issues.push(OggIssue::BadChecksum(index));
+        }
+
+        let serial = *serial.get_or_insert(page.header.serial);
+        if page.header.serial != serial || (index != 0 && page.has_flag(PAGE_BEGIN_OF_STREAM)) {
+            issues.push(OggIssue::SerialMismatch(index));
+            continue;
+        }
+
+        if let Some(previous) = pages.last().map(|p: &OggPage| p.header.sequence) {
+            let expected = previous.wrapping_add(1);
+            if page.header.sequence != expected {
+                issues.push(OggIssue::SequenceGap {
+                    page: index,
+                    expected,
+                    found: page.header.sequence,
+                });
+            }
+        }
+
+        if page.header.granule_position != u64::MAX {
+            if page.header.granule_position < last_granule {
+                issues.push(OggIssue::GranuleNotMonotonic(index));
+            }
+            last_granule = page.header.granule_position;
+        }
+
+        if page.has_flag(PAGE_CONTINUED) != partial.is_some() {
+            issues.push(OggIssue::BrokenContinuation(index));
+        }
+
+        for (i, (data, finished)) in page.packets().into_iter().enumerate() {
+            let mut packet = match partial.take() {
+                // a continued packet can only be on the start of the page
+                Some(packet) if i == 0 && page.has_flag(PAGE_CONTINUED) => packet,
+                // the beginning of this packet got lost
+                None if i == 0 && page.has_flag(PAGE_CONTINUED) => continue,
+                _ => Packet {
+                    data: Vec::new(),
+                    first_page: index,
+                    last_page: index,
+                },
+            };
+            packet.data.extend_from_slice(data);
+            packet.last_page = index;
+
+            if finished {
+                packets.push(packet);
+            } else {
+                partial = Some(packet);
+            }
+        }
+
+        pages.push(page);
+    }
+
+    Demuxed {
+        pages,
+        packets,
+        issues,
+    }
+}
+
+/// Check the ogg vorbis data for problems that can make the game misbehave, returns
+/// an empty list if the data is fine.
+///
+/// It checks the checksum, serial number, sequence number and granule position of
+/// each page, the begin and end of stream flags and the layout of the vorbis headers.
+pub fn validate(bytes: &[u8]) -> Vec<OggIssue> {
+    let Demuxed {
+        pages,
+        packets,
+        mut issues,
+    } = demux(bytes);
+
+    if !pages
+        .first()
+        .is_some_and(|p| p.has_flag(PAGE_BEGIN_OF_STREAM))
+    {
+        issues.push(OggIssue::MissingBeginOfStream);
+    }
+    if !pages.last().is_some_and(|p| p.has_flag(PAGE_END_OF_STREAM)) {
+        issues.push(OggIssue::MissingEndOfStream);
+    }
+
+    let headers_found = packets.len() >= VORBIS_HEADERS.len()
+        && packets
+            .iter()
+            .zip(VORBIS_HEADERS)
+            .all(|(packet, signature)| packet.data.starts_with(signature));
+
+    if !headers_found {
+        issues.push(OggIssue::MissingVorbisHeaders);
+    } else {
+        let ident_alone = pages[0].packets().len() == 1 && packets[0].last_page == 0;
+        let audio_on_new_page = packets
+            .get(VORBIS_HEADERS.len())
+            .is_none_or(|audio| audio.first_page > packets[2].last_page);
+
+        if !ident_alone || !audio_on_new_page {
+            issues.push(OggIssue::HeadersNotOnOwnPages);
+        }
+    }
+
+    issues
+}
+
+/// Rewrite the pages of the ogg vorbis data into a layout that is safe for the game.
+///
+/// Only the packets of the first logical stream are kept, the identification header
+/// get its own page, the comment and setup headers start on a new page and the audio
+/// packets are written on pages of around 4KB. the sequence numbers, granule positions
+/// (calculated from the audio packets), flags and checksums of all the pages get regenerated.
+#[cfg(feature = "ogg")]
+pub fn repair(bytes: &[u8]) -> Result<Vec<u8>> {
+    use lewton::{
+        audio::get_decoded_sample_count,
+        header::{read_header_ident, read_header_setup},
+        VorbisError,
+    };
+
+    let Demuxed { pages, packets, .. } = demux(bytes);
+    let Some(serial) = pages.first().map(|p| p.header.serial) else {
+        return Err(Error::InvalidOgg("no ogg page found".to_owned()));
+    };
+    // the original end trim, only used if it's still valid after the repair
+    let original_end = pages
+        .iter()
+        .rev()
+        .map(|p| p.header.granule_position)
+        .find(|&g| g != u64::MAX);
+
+    let [ident, comment, setup, audio @ ..] = packets.as_slice() else {
+        return Err(Error::InvalidOgg("vorbis headers are missing".to_owned()));
+    };
+    if audio.is_empty() {
+        return Err(Error::InvalidOgg("no audio packet found".to_owned()));
+    }
+
+    let ident_header = read_header_ident(&ident.data).map_err(VorbisError::from)?;
+    let setup_header = read_header_setup(
+        &setup.data,
+        ident_header.audio_channels,
+        (ident_header.blocksize_0, ident_header.blocksize_1),
+    )
+    .map_err(VorbisError::from)?;
+    if !comment.data.starts_with(VORBIS_HEADERS[1]) {
+        return Err(Error::InvalidOgg(
+            "vorbis comment header is missing".to_owned(),
+        ));
+    }
+
+    let mut writer = PageWriter::new(serial);
+
+    writer.push_packet(&ident.data, 0)?;
+    writer.flush_page(false)?;
+    writer.push_packet(&comment.data, 0)?;
+    writer.push_packet(&setup.data, 0)?;
+    writer.flush_page(false)?;
+
+    let mut granule = 0;
+    let mut last_count = 0;
+    for (i, packet) in audio.iter().enumerate() {
+        // the first audio packet only prime the decoder
+        if i != 0 {
+            last_count = get_decoded_sample_count(&ident_header, &setup_header, &packet.data)
+                .map_err(VorbisError::from)? as u64;
+            granule += last_count;
+        }
+
+        if i == audio.len() - 1 {
+            if let Some(end) = original_end.filter(|&e| e <= granule && e + last_count > granule) {
+                granule = end;
+            }
+        }
+
+        if writer.data.len() >= TARGET_PAGE_SIZE {
+            writer.flush_page(false)?;
+        }
+
+        writer.push_packet(&packet.data, granule)?;
+    }
+    writer.flush_page(true)?;
+
+    Ok(writer.output)
+}
+
+/// Helper for writing packets into ogg pages.
+#[cfg(feature = "ogg")]
+struct PageWriter {
+    serial: u32,
+    sequence: u32,
+    /// Granule of the last packet that finished on the current page.
+    granule: u64,
+    continued: bool,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+    output: Vec<u8>,
+}
+
+#[cfg(feature = "ogg")]
+impl PageWriter {
+    fn new(serial: u32) -> Self {
+        Self {
+            serial,
+            sequence: 0,
+            granule: u64::MAX,
+            continued: false,
+            segments: Vec::new(),
+            data: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Add the packet to the current page, packets that don't fit get continued on the next pages.
+    fn push_packet(&mut self, mut packet: &[u8], granule: u64) -> Result<()> {
+        let mut started = false;
+        loop {
+            if self.segments.len() == 255 {
+                self.flush_page(false)?;
+                self.continued = started;
+            }
+
+            let len = packet.len().min(255);
+            self.segments.push(len as u8);
+            self.data.extend_from_slice(&packet[..len]);
+            packet = &packet[len..];
+            started = true;
+
+            // a segment smaller than 255 finish the packet
+            if len < 255 {
+                self.granule = granule;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Write the current page to the output, does nothing if the page is empty.
+    fn flush_page(&mut self, end_of_stream: bool) -> Result<()> {
+        if self.segments.is_empty() {
+            return Ok(());
+        }
+
+        let mut header_type = 0;
+        if self.continued {
+            header_type |= PAGE_CONTINUED;
+        }
+        if self.sequence == 0 {
+            header_type |= PAGE_BEGIN_OF_STREAM;
+        }
+        if end_of_stream {
+            header_type |= PAGE_END_OF_STREAM;
+        }
+
+        let mut page = OggPage {
+            header: OggPageHeader {
+                version: 0,
+                header_type,
+                granule_position: self.granule,
+                serial: self.serial,
+                sequence: self.sequence,
+                checksum: 0,
+                segment_table: std::mem::take(&mut self.segments),
+            },
+            data: std::mem::take(&mut self.data),
+        };
+        page.header.checksum = page.calculate_checksum()?;
+
+        let mut writer = Cursor::new(&mut self.output);
+        writer.set_position(writer.get_ref().len() as u64);
+        page.write(&mut writer)?;
+
+        self.sequence += 1;
+        self.granule = u64::MAX;
+        self.continued = false;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn page(sequence: u32, header_type: u8, granule_position: u64, data: &[u8]) -> OggPage {
+        let mut page = OggPage {
+            header: OggPageHeader {
+                version: 0,
+                header_type,
+                granule_position,
+                serial: 7,
+                sequence,
+                checksum: 0,
+                segment_table: vec![data.len() as u8],
+            },
+            data: data.to_vec(),
+        };
+        page.header.checksum = page.calculate_checksum().unwrap();
+        page
+    }
+
+    fn to_bytes(pages: &[OggPage]) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        for page in pages {
+            page.write(&mut bytes).unwrap();
+        }
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn validate_pages() {
+        let mut pages = vec![
+            page(0, PAGE_BEGIN_OF_STREAM, 0, b"\x01vorbis ident"),
+            page(1, 0, 0, b"\x03vorbis comment"),
+            page(2, 0, 0, b"\x05vorbis setup"),
+            page(3, 0, 1000, b"audio"),
+            page(4, PAGE_END_OF_STREAM, 2000, b"audio"),
+        ];
+        assert_eq!(validate(&to_bytes(&pages)), []);
+
+        pages[3].header.granule_position = 3000;
+        pages[3].header.checksum = pages[3].calculate_checksum().unwrap();
+        pages[4].header.sequence = 5;
+        pages[4].header.header_type = 0;
+        assert_eq!(
+            validate(&to_bytes(&pages)),
+            [
+                OggIssue::BadChecksum(4),
+                OggIssue::SequenceGap {
+                    page: 4,
+                    expected: 4,
+                    found: 5
+                },
+                OggIssue::GranuleNotMonotonic(4),
+                OggIssue::MissingEndOfStream,
+            ]
+        );
+
+        let mut bytes = to_bytes(&pages[..2]);
+        bytes.extend_from_slice(b"garbage");
+        assert_eq!(
+            validate(&bytes),
+            [
+                OggIssue::InvalidPage(bytes.len() as u64 - 7),
+                OggIssue::MissingEndOfStream,
+                OggIssue::MissingVorbisHeaders,
+            ]
+        );
+    }
+
+    #[cfg(feature = "ogg")]
+    #[test]
+    fn page_writer() {
+        let mut writer = PageWriter::new(7);
+        writer.push_packet(b"\x01vorbis ident", 0).unwrap();
+        writer.flush_page(false).unwrap();
+        writer.push_packet(b"\x03vorbis comment", 0).unwrap();
+        writer.push_packet(b"\x05vorbis setup", 0).unwrap();
+        writer.flush_page(false).unwrap();
+        // big enough to get split over two pages
+        writer.push_packet(&[0; 255 * 300], 1000).unwrap();
+        writer.push_packet(&[0; 10], 2000).unwrap();
+        writer.flush_page(true).unwrap();
+
+        let pages: Vec<_> = OggPages::new(&writer.output)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(pages.len(), 4);
+        assert_eq!(pages[2].header.granule_position, u64::MAX);
+        assert!(pages[3].has_flag(PAGE_CONTINUED));
+        assert_eq!(pages[3].header.granule_position, 2000);
+        assert_eq!(validate(&writer.output), []);
+    }
+}
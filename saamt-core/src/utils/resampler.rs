@@ -0,0 +1,175 @@
+//! A band-limited resampler for changing the sample rate of pcm samples.
+//!
+//! The samples get interpolated using a Kaiser windowed sinc filter, the filter cutoff follow
+//! the lower of the two sample rates so downsampling doesn't cause aliasing.
+
+/// Number of zero crossings of the sinc on each side of the filter, higher is sharper but slower.
+const ZERO_CROSSINGS: usize = 32;
+/// Number of precomputed points of the filter between each zero crossing.
+const RESOLUTION: usize = 512;
+/// Cutoff of the filter relative to the lower nyquist frequency, a bit below 1 so
+/// the transition band of the window doesn't alias.
+const ROLLOFF: f64 = 0.945;
+/// Beta of the Kaiser window, around 90dB of stopband attenuation.
+const KAISER_BETA: f64 = 9.0;
+
+/// Resample interleaved 16-bit samples with the given number of `channels` from the `from`
+/// sample rate to the `to` sample rate.
+///
+/// Returns the samples as they are if both sample rates are the same.
+pub fn resample(samples: &[i16], channels: u16, from: u32, to: u32) -> Vec<i16> {
+    let channels = channels.max(1) as usize;
+    if from == to || from == 0 || to == 0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let frames = samples.len() / channels;
+    let out_frames = (frames as u64 * to as u64).div_ceil(from as u64) as usize;
+
+    let cutoff = ROLLOFF * (to as f64 / from as f64).min(1.0);
+    // number of input samples on each side of the output sample that affect it
+    let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as isize;
+    let table = filter_table();
+
+    let mut output = vec![0; out_frames * channels];
+    for frame in 0..out_frames {
+        // position of the output sample inside the input
+        let position = (frame as u64 * from as u64) as f64 / to as f64;
+        let center = position.floor() as isize;
+
+        let first = (center - half_width + 1).max(0);
+        let last = (center + half_width).min(frames as isize - 1);
+
+        for channel in 0..channels {
+            let mut sum = 0.0;
+            let mut weights = 0.0;
+
+            for input in first..=last {
+                let weight = filter(&table, (position - input as f64).abs() * cutoff);
+                sum += samples[input as usize * channels + channel] as f64 * weight;
+                weights += weight;
+            }
+
+            // normalizing by the sum of the weights keep the gain at 1, even near the edges
+            if weights.abs() > f64::EPSILON {
+                output[frame * channels + channel] = (sum / weights)
+                    .round()
+                    .clamp(i16::MIN as f64, i16::MAX as f64)
+                    as i16;
+            }
+        }
+    }
+
+    output
+}
+
+/// Precompute the windowed sinc from zero to [`ZERO_CROSSINGS`].
+fn filter_table() -> Vec<f64> {
+    let i0_beta = bessel_i0(KAISER_BETA);
+
+    (0..=ZERO_CROSSINGS * RESOLUTION + 1)
+        .map(|i| {
+            let x = i as f64 / RESOLUTION as f64;
+            if x >= ZERO_CROSSINGS as f64 {
+                return 0.0;
+            }
+
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+            };
+            let ratio = x / ZERO_CROSSINGS as f64;
+            let window = bessel_i0(KAISER_BETA * (1.0 - ratio * ratio).sqrt()) / i0_beta;
+
+            sinc * window
+        })
+        .collect()
+}
+
+/// Get the value of the filter at `x` zero crossings away from the center
+/// by linear interpolation of the table.
+fn filter(table: &[f64], x: f64) -> f64 {
+    let position = x * RESOLUTION as f64;
+    let index = position as usize;
+    if index + 1 >= table.len() {
+        return 0.0;
+    }
+
+    let fraction = position - index as f64;
+    table[index] + (table[index + 1] - table[index]) * fraction
+}
+
+/// Modified Bessel function of the first kind of order zero, used by the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+
+    for k in 1..50 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: u32, seconds: f64) -> Vec<i16> {
+        (0..(sample_rate as f64 * seconds) as usize)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                ((2.0 * std::f64::consts::PI * frequency * t).sin() * 16000.0) as i16
+            })
+            .collect()
+    }
+
+    fn rms(samples: &[i16]) -> f64 {
+        let sum: f64 = samples.iter().map(|&s| (s as f64).powi(2)).sum();
+        (sum / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn resample_sine() {
+        let input = sine(1000.0, 44100, 0.5);
+        let output = resample(&input, 1, 44100, 22050);
+        assert_eq!(output.len(), input.len() / 2);
+
+        // compare with a sine generated at the new rate, skipping the edges
+        let expected = sine(1000.0, 22050, 0.5);
+        let middle = 500..output.len() - 500;
+        let max_error = output[middle.clone()]
+            .iter()
+            .zip(&expected[middle])
+            .map(|(&a, &b)| (a as i32 - b as i32).abs())
+            .max()
+            .unwrap();
+        assert!(max_error < 40, "max error was {max_error}");
+
+        // stereo channels are resampled separately
+        let stereo: Vec<i16> = input.iter().flat_map(|&s| [s, -s]).collect();
+        let output = resample(&stereo, 2, 44100, 96000);
+        assert_eq!(output.len(), (input.len() * 96000).div_ceil(44100) * 2);
+        assert!(output
+            .chunks(2)
+            .all(|f| (f[0] as i32 + f[1] as i32).abs() <= 1));
+
+        assert_eq!(resample(&input, 1, 44100, 44100), input);
+    }
+
+    #[test]
+    fn resample_filters_high_frequencies() {
+        // a 15kHz tone can't exist at 22050Hz, it should be removed instead of aliasing
+        let input = sine(15000.0, 96000, 0.5);
+        let output = resample(&input, 1, 96000, 22050);
+
+        assert!(rms(&input) > 10000.0);
+        assert!(rms(&output[200..output.len() - 200]) < 50.0);
+    }
+}
@@ -59,7 +59,11 @@ impl<'a> VAG2WAVDecoder<'a> {
 
         let samples: Vec<i16> = self.decoder().flatten().collect();
 
-        Wav { samples, spec }
+        Wav {
+            samples,
+            spec,
+            loop_start: None,
+        }
     }
 }
 
@@ -5,7 +5,7 @@
 use std::{
     ffi::OsStr,
     fs::File,
-    io::{BufReader, Read, Seek, SeekFrom},
+    io::{BufReader, Seek, SeekFrom},
     path::Path,
 };
 
@@ -13,7 +13,10 @@ use hound::{read_wave_header, WavReader, WavSpec};
 
 use crate::{
     error::*,
-    utils::vag::{VAGChunk, VAG_SAMPLE_BYTES},
+    utils::{
+        vag::{VAGChunk, VAG_SAMPLE_BYTES},
+        wav::{read_sample_loop, read_samples, Wav},
+    },
 };
 
 use super::{PackInfo, VAGFlag, Vag, VagAudio, VAG_SAMPLE_NIBBL};
@@ -62,15 +65,25 @@ pub struct WAV2VAGEncoder {
 
 impl WAV2VAGEncoder {
     /// Create a new wav encoder that will encode wav samples to vag
-    /// keep in mind that we only support mono files and PCM.
+    /// keep in mind that we only support mono files, PCM and float samples
+    /// get converted to 16-bit without any dither.
     pub fn new(wav_path: &Path, loop_mode: LoopMode) -> Result<Self> {
+        Self::new_with_dither(wav_path, loop_mode, false)
+    }
+
+    /// The same as [`WAV2VAGEncoder::new`], but TPDF dither get added to the samples
+    /// that lose precision when converted to 16-bit if `dither` is true.
+    pub fn new_with_dither(wav_path: &Path, loop_mode: LoopMode, dither: bool) -> Result<Self> {
         let mut wav_reader = BufReader::new(File::open(wav_path)?);
 
         if let Err(error) = read_wave_header(&mut wav_reader) {
             return Err(Error::InvalidWav(error.to_string()));
         }
 
-        let loop_start_end = match try_read_sample_chunk(&mut wav_reader) {
+        // seek back to start of wav because we want to parse it again
+        wav_reader.seek(SeekFrom::Start(0))?;
+
+        let loop_start_end = match read_sample_loop(&mut wav_reader) {
             Ok(Some((ld1, ld2))) => (
                 get_loop_offset(ld1).wrapping_sub(1) as usize,
                 get_loop_offset(ld2).wrapping_sub(2) as usize,
@@ -78,34 +91,53 @@ impl WAV2VAGEncoder {
             _ => (0, usize::MAX),
         };
 
-        // seek back to start of wav because we want to parse it again
         wav_reader.seek(SeekFrom::Start(0))?;
 
-        let wav = WavReader::new(wav_reader)?;
+        let (spec, samples) = read_samples(WavReader::new(wav_reader)?, dither)?;
+
+        let name = wav_path
+            .with_extension("")
+            .file_name()
+            .and_then(OsStr::to_str)
+            .unwrap_or_default()
+            .to_owned();
 
-        let spec: WavSpec = wav.spec();
+        Self::from_parts(name, spec, samples, loop_start_end, loop_mode)
+    }
 
+    /// Create a new encoder from an already loaded wav, the same as [`WAV2VAGEncoder::new`]
+    /// we only support mono files.
+    ///
+    /// as there is no smpl chunk here, [`LoopMode::FromInput`] will loop the whole wav.
+    pub fn from_wav(name: impl Into<String>, wav: &Wav, loop_mode: LoopMode) -> Result<Self> {
+        Self::from_parts(
+            name.into(),
+            wav.spec,
+            wav.samples.clone(),
+            (0, usize::MAX),
+            loop_mode,
+        )
+    }
+
+    fn from_parts(
+        name: String,
+        spec: WavSpec,
+        mut samples: Vec<i16>,
+        loop_start_end: (usize, usize),
+        loop_mode: LoopMode,
+    ) -> Result<Self> {
         if spec.channels != 1 {
             return Err(Error::InvalidWav(
                 "Wav with more then one channels aren't supported".to_owned(),
             ));
         }
 
-        let mut samples: Vec<i16> = wav.into_samples().collect::<std::result::Result<_, _>>()?;
-
-        // make sure that samples is in pow of `VAG_SAMPLE_NIBBL`
-        let rs = samples.len() % VAG_SAMPLE_NIBBL;
-        if rs != 0 {
-            samples.extend(vec![0; rs]);
-        }
+        // pad the samples to a multiple of `VAG_SAMPLE_NIBBL`, the last chunk would
+        // get dropped otherwise
+        samples.resize(samples.len().next_multiple_of(VAG_SAMPLE_NIBBL), 0);
 
         Ok(Self {
-            name: wav_path
-                .with_extension("")
-                .file_name()
-                .and_then(OsStr::to_str)
-                .unwrap_or_default()
-                .to_owned(),
+            name,
             spec,
             samples,
             loop_start_end,
@@ -317,49 +349,74 @@ impl SampleEncoder {
     }
 }
 
-// this function doesn't check if the file is a valid wav or not.
-// this is not a good way, because in this way we are reading the
-// wav file two time, but I really don't want to change how hound
-// work at the moment...
-fn try_read_sample_chunk(reader: &mut BufReader<File>) -> Result<Option<(u32, u32)>> {
-    use binrw::BinRead;
-
-    let mut chunk_id = [0_u8; 4];
-
-    while reader.read_exact(&mut chunk_id).is_ok() {
-        let len = u32::read_le(reader)?;
-
-        if &chunk_id == b"smpl" {
-            reader.seek_relative(12)?;
-            let _midi_note = i32::read_le(reader)?;
-            reader.seek_relative(16)?;
-            let number_of_samples = i32::read_le(reader)?;
-            reader.seek_relative(8)?;
-
-            let mut loop_info = (0, 0);
-            for _ in 0..number_of_samples {
-                // Read Chunk info
-                let _cue_point_id = i32::read_le(reader)?;
-                let _loop_type = i32::read_le(reader)?; // 0 = loop forward, 1 = alternating loop, 2 = reverse
-
-                let start = u32::read_le(reader)?;
-                let end = u32::read_le(reader)?;
-                let _fraction = i32::read_le(reader)?;
-                let _play_count = i32::read_le(reader)?;
-
-                // Save Data
-                loop_info = (start, end);
-            }
+fn get_loop_offset(loop_offset: u32) -> u32 {
+    loop_offset / 28 + if loop_offset % 28 != 0 { 2 } else { 1 }
+}
 
-            return Ok(Some(loop_info));
-        } else {
-            reader.seek_relative(len as i64)?;
-        }
+#[cfg(test)]
+mod test {
+    use hound::SampleFormat;
+
+    use super::*;
+
+    #[test]
+    fn trailing_chunks() {
+        let wav = Wav {
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: (0..VAG_SAMPLE_NIBBL as i16 * 4).map(|i| i * 100).collect(),
+            loop_start: None,
+        };
+
+        let flags = |loop_mode| {
+            WAV2VAGEncoder::from_wav("test", &wav, loop_mode)
+                .unwrap()
+                .generate_vag()
+                .0
+                .chunks
+                .into_iter()
+                .map(|c| c.flags)
+                .collect::<Vec<_>>()
+        };
+
+        // the encoder doesn't add a terminating chunk, the last chunk of the audio end
+        // the vag in both cases
+        let flags_no_loop = flags(LoopMode::ForceNoLoop);
+        assert_eq!(flags_no_loop.len(), 4);
+        assert_eq!(flags_no_loop.last(), Some(&VAGFlag::LoopLastBlock));
+
+        // without a smpl chunk the whole wav loop
+        let flags_loop = flags(LoopMode::FromInput);
+        assert_eq!(flags_loop.len(), 4);
+        assert!(flags_loop.contains(&VAGFlag::LoopStart));
+        assert_eq!(flags_loop.last(), Some(&VAGFlag::LoopEnd));
     }
+    #[test]
+    fn pad_last_chunk() {
+        let wav = Wav {
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: vec![1000; VAG_SAMPLE_NIBBL + 2],
+            loop_start: None,
+        };
 
-    Ok(None)
-}
+        let chunks = WAV2VAGEncoder::from_wav("test", &wav, LoopMode::ForceNoLoop)
+            .unwrap()
+            .generate_vag()
+            .0
+            .chunks;
 
-fn get_loop_offset(loop_offset: u32) -> u32 {
-    loop_offset / 28 + if loop_offset % 28 != 0 { 2 } else { 1 }
+        // the 2 extra samples get their own chunk, which end the vag
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].flags, VAGFlag::Nothing);
+        assert_eq!(chunks[1].flags, VAGFlag::LoopLastBlock);
+    }
 }
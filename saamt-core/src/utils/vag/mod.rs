@@ -88,14 +88,19 @@ impl VagAudio {
     /// at this point we expect the vag to be valid,
     /// so we will panic in any kind of error.
     pub fn raw_vag_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity((self.0.chunks.len() * size_of::<VAGChunk>()) + 16);
+        bytes.extend_from_slice(&self.0.vag_header);
+        bytes.extend(self.raw_chunk_bytes());
+
+        bytes
+    }
+
+    /// Get only the vag chunks bytes, without vag header and
+    /// the 16 byte block that come before the chunks.
+    pub fn raw_chunk_bytes(&self) -> Vec<u8> {
         let vag = &self.0;
 
-        let mut writer = Cursor::new(Vec::with_capacity(
-            (vag.chunks.len() * size_of::<VAGChunk>()) + 16,
-        ));
-        writer
-            .write_all(&vag.vag_header)
-            .expect("Failed to write vag header");
+        let mut writer = Cursor::new(Vec::with_capacity(vag.chunks.len() * size_of::<VAGChunk>()));
 
         for chunk in vag.chunks.iter() {
             chunk
@@ -110,6 +115,67 @@ impl VagAudio {
     pub fn decoder(&self) -> VAG2WAVDecoder {
         VAG2WAVDecoder::new(self)
     }
+
+    /// Start of the loop (in samples) based on the chunk that have the loop start flag,
+    /// `None` if the vag doesn't loop.
+    ///
+    /// The loop always start at the beginning of a chunk, so this is a multiple of 28.
+    pub fn loop_start(&self) -> Option<u32> {
+        self.0
+            .chunks
+            .iter()
+            .position(|c| c.flags == VAGFlag::LoopStart)
+            .map(|idx| (idx * VAG_SAMPLE_NIBBL) as u32)
+    }
+
+    /// Update the loop flags of the chunks so the vag loop from the chunk that have the
+    /// `loop_start` sample until the last chunk, or doesn't loop at all if its `None`.
+    ///
+    /// The flags of a vag that doesn't loop are left untouched if `loop_start` is `None`.
+    pub fn set_loop(&mut self, loop_start: Option<u32>) {
+        if loop_start.is_none() && self.loop_start().is_none() {
+            return;
+        }
+
+        let chunks = &mut self.0.chunks;
+        // the terminating chunks come after the last chunk of the audio
+        let Some(last) = chunks.iter().rposition(|c| c.flags != VAGFlag::PlaybackEnd) else {
+            return;
+        };
+
+        let start =
+            loop_start.map(|start| (start as usize / VAG_SAMPLE_NIBBL).min(last.saturating_sub(1)));
+
+        for (idx, chunk) in chunks[..=last].iter_mut().enumerate() {
+            chunk.flags = match start {
+                Some(_) if idx == last => VAGFlag::LoopEnd,
+                Some(start) if idx == start => VAGFlag::LoopStart,
+                Some(start) if idx > start => VAGFlag::LoopRegion,
+                None if idx == last => VAGFlag::LoopLastBlock,
+                _ => VAGFlag::Nothing,
+            };
+        }
+    }
+
+    /// Append the terminating chunk that stop the playback after the last chunk of the
+    /// audio, like the sounds of the game have. Nothing get added if the vag already end
+    /// with one.
+    pub fn add_playback_end(&mut self) {
+        let chunks = &mut self.0.chunks;
+        let Some(pack_infos) = chunks
+            .last()
+            .filter(|c| c.flags != VAGFlag::PlaybackEnd)
+            .map(|c| c.pack_infos)
+        else {
+            return;
+        };
+
+        chunks.push(VAGChunk {
+            pack_infos,
+            flags: VAGFlag::PlaybackEnd,
+            sample: Default::default(),
+        });
+    }
 }
 
 impl Debug for VagAudio {
@@ -158,40 +224,46 @@ impl Vag {
 
         let (vag_header, data) = data.split_at(16);
 
-        assert!(
-            data.len() % size_of::<VAGChunk>() == 0,
-            "Invalid vag, data size doesn't fit a valid number of Chunks."
-        );
+        let vag = Self::new_from_raw_chunks(sample_rate, name, data)
+            .unwrap_or_else(|error| panic!("{error}"));
+
+        Self {
+            vag_header: vag_header.try_into().unwrap(),
+            ..vag
+        }
+    }
+
+    /// Create a new vag from the raw chunk bytes, unlike [`Vag::new`]
+    /// `data` shouldn't start with the 16 byte header.
+    ///
+    /// Returns an error if the size of `data` isn't a multiple of the chunk size or
+    /// a chunk have an invalid flag.
+    pub fn new_from_raw_chunks(sample_rate: u32, name: [u8; 16], data: &[u8]) -> Result<Self> {
+        if !data.len().is_multiple_of(size_of::<VAGChunk>()) {
+            return Err(Error::InvalidVag(format!(
+                "data size ({}) doesn't fit a valid number of chunks",
+                data.len()
+            )));
+        }
 
         let chunks: Vec<VAGChunk> = data
             .chunks_exact(16)
-            .map(|slice| VAGChunk {
-                pack_infos: PackInfo(slice[0]),
-                flags: VAGFlag::try_from(slice[1]).expect("Invalid flag value in VAG"),
-                sample: slice[2..16]
-                    .try_into()
-                    .expect("Failed to read samples to VAGChunk."),
+            .map(|slice| {
+                Ok(VAGChunk {
+                    pack_infos: PackInfo(slice[0]),
+                    flags: VAGFlag::try_from(slice[1])
+                        .map_err(|flag| Error::InvalidVag(format!("invalid chunk flag {flag}")))?,
+                    sample: slice[2..16]
+                        .try_into()
+                        .expect("Failed to read samples to VAGChunk."),
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        Self {
-            version: 0x20,
-            ssa: 0x0,
-            sample_rate,
-            vol_left: 0,
-            vol_right: 0,
-            pitch: 0,
-            adsr1: 0,
-            adsr2: 0,
-            channels: 0,
-            name,
-            vag_header: vag_header.try_into().unwrap(),
-            chunks,
-        }
+        Ok(Self::new_from_chunks(sample_rate, name, chunks))
     }
 
     /// Create a new vag from the chunks
-    #[cfg(feature = "wav")]
     pub fn new_from_chunks(sample_rate: u32, name: [u8; 16], chunks: Vec<VAGChunk>) -> Self {
         Self {
             version: 0x20,
@@ -3,38 +3,133 @@
 
 use std::{
     fs::File,
-    io::{BufWriter, Read, Seek, Write},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
-use binrw::io::BufReader;
-use hound::{WavReader, WavSpec, WavWriter};
+use binrw::{binrw, io::BufReader, BinRead, BinWrite};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
 
 use crate::error::*;
 
+use super::resampler::resample;
+
 /// Wav audio
 #[derive(Clone)]
 pub struct Wav {
     pub(crate) spec: WavSpec,
     pub(crate) samples: Vec<i16>,
+    /// Start of the loop (in samples), the loop always end at the end of the audio.
+    pub(crate) loop_start: Option<u32>,
+}
+
+/// How the channels of a wav get mixed down into a single mono channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Downmix {
+    /// Average of all the channels.
+    #[default]
+    Average,
+    /// Only keep the left (first) channel.
+    Left,
+    /// Only keep the right (second) channel.
+    Right,
+    /// Sum of all the channels with equal power (`-3dB` per channel for stereo), the
+    /// whole audio get turned down if the sum clip so it always have some headroom.
+    Mid,
+}
+
+/// Body of the `smpl` chunk, hound doesn't support it so we read and write it ourself.
+#[binrw]
+#[brw(little)]
+struct SampleChunk {
+    manufacturer: u32,
+    product: u32,
+    /// Duration of each sample in nanoseconds.
+    sample_period: u32,
+    midi_unity_note: u32,
+    midi_pitch_fraction: u32,
+    smpte_format: u32,
+    smpte_offset: u32,
+    #[br(temp)]
+    #[bw(calc = loops.len() as u32)]
+    num_loops: u32,
+    sampler_data: u32,
+    #[br(count = num_loops)]
+    loops: Vec<SampleLoop>,
+}
+
+/// A loop inside the `smpl` chunk.
+#[binrw]
+#[brw(little)]
+struct SampleLoop {
+    cue_point_id: u32,
+    /// 0 = loop forward, 1 = alternating loop, 2 = reverse
+    loop_type: u32,
+    start: u32,
+    /// Last sample of the loop, it get played too.
+    end: u32,
+    fraction: u32,
+    /// 0 = infinite
+    play_count: u32,
+}
+
+impl SampleChunk {
+    /// Size of the chunk with a single loop.
+    const SIZE: u32 = 36 + 24;
+
+    /// Create a `smpl` chunk with a single forward loop.
+    fn with_loop(sample_rate: u32, start: u32, end: u32) -> Self {
+        Self {
+            manufacturer: 0,
+            product: 0,
+            sample_period: 1_000_000_000 / sample_rate.max(1),
+            midi_unity_note: 60,
+            midi_pitch_fraction: 0,
+            smpte_format: 0,
+            smpte_offset: 0,
+            sampler_data: 0,
+            loops: vec![SampleLoop {
+                cue_point_id: 0,
+                loop_type: 0,
+                start,
+                end,
+                fraction: 0,
+                play_count: 0,
+            }],
+        }
+    }
 }
 
 impl Wav {
-    /// Read and create a Wav from input reader
+    /// Read and create a Wav from input reader, see [`Wav::with_dither`].
     pub fn new<R: Read + Seek>(reader: R) -> Result<Self> {
-        let reader = WavReader::new(reader)?;
+        Self::with_dither(reader, false)
+    }
+
+    /// Read and create a Wav from input reader, 8 to 32-bit PCM and 32-bit float wavs
+    /// are supported and the samples always get converted to 16-bit.
+    ///
+    /// If `dither` is true TPDF dither get added to the samples that lose precision
+    /// in the conversion (24/32-bit and float ones).
+    pub fn with_dither<R: Read + Seek>(mut reader: R, dither: bool) -> Result<Self> {
+        let loop_start = read_sample_loop(&mut reader)?.map(|(start, _)| start);
+        let (spec, samples) = read_samples(WavReader::new(reader)?, dither)?;
         Ok(Self {
-            spec: reader.spec(),
-            samples: reader
-                .into_samples()
-                .collect::<std::result::Result<_, _>>()?,
+            spec,
+            samples,
+            loop_start,
         })
     }
 
     /// a helper method for reading the wav file from a file directly.
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_file_with_dither(path, false)
+    }
+
+    /// a helper method for reading the wav file from a file directly, see [`Wav::with_dither`].
+    pub fn from_file_with_dither(path: impl AsRef<Path>, dither: bool) -> Result<Self> {
         let reader = BufReader::new(File::open(path)?);
-        Self::new(reader)
+        Self::with_dither(reader, dither)
     }
 
     /// Return specifies properties of the audio data.
@@ -47,17 +142,106 @@ impl Wav {
         &self.samples
     }
 
-    /// Write the wav file to the input writer
-    pub fn to_writer<W: Write + Seek>(&self, writer: W) -> Result<()> {
-        let mut writer = WavWriter::new(writer, self.spec)?;
-        let mut i16_writer = writer.get_i16_writer(self.samples.len() as _);
+    /// Return the start of the loop (in samples) from the `smpl` chunk, if the wav have one.
+    pub fn loop_start(&self) -> Option<u32> {
+        self.loop_start
+    }
+
+    /// Return a copy of the wav resampled to the given `sample_rate`, the loop start
+    /// get moved to the matching sample.
+    ///
+    /// See [`resampler`](super::resampler) for more info.
+    pub fn resample(&self, sample_rate: u32) -> Wav {
+        let from = self.spec.sample_rate;
+        let samples = resample(&self.samples, self.spec.channels, from, sample_rate);
+        let loop_start = self
+            .loop_start
+            .map(|start| (start as u64 * sample_rate as u64 / from.max(1) as u64) as u32);
+
+        Wav {
+            spec: WavSpec {
+                sample_rate,
+                ..self.spec
+            },
+            samples,
+            loop_start,
+        }
+    }
+
+    /// Return a mono copy of the wav with its channels mixed down based on the
+    /// given [`Downmix`], mono wavs are returned as they are.
+    pub fn downmix(&self, downmix: Downmix) -> Wav {
+        let channels = self.spec.channels as usize;
+        if channels <= 1 {
+            return self.clone();
+        }
+
+        let frames = self.samples.chunks_exact(channels);
+        let samples = match downmix {
+            Downmix::Average => frames
+                .map(|f| (f.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16)
+                .collect(),
+            Downmix::Left => frames.map(|f| f[0]).collect(),
+            Downmix::Right => frames.map(|f| f[1]).collect(),
+            Downmix::Mid => {
+                let scale = (channels as f64).sqrt().recip();
+                let mid: Vec<f64> = frames
+                    .map(|f| f.iter().map(|&s| s as f64).sum::<f64>() * scale)
+                    .collect();
+
+                // turn down the whole audio if the peak doesn't fit anymore
+                let peak = mid.iter().fold(0.0_f64, |peak, s| peak.max(s.abs()));
+                let gain = (i16::MAX as f64 / peak).min(1.0);
+
+                mid.into_iter().map(|s| (s * gain).round() as i16).collect()
+            }
+        };
+
+        Wav {
+            spec: WavSpec {
+                channels: 1,
+                ..self.spec
+            },
+            samples,
+            loop_start: self.loop_start,
+        }
+    }
+
+    /// Write the wav file to the input writer, a `smpl` chunk get added
+    /// at the end if the wav have a loop that start inside the audio.
+    pub fn to_writer<W: Write + Seek>(&self, mut writer: W) -> Result<()> {
+        let frames = self.samples.len() / self.spec.channels.max(1) as usize;
+        let loop_start = self.loop_start.filter(|&start| (start as usize) < frames);
+
+        let start = writer.stream_position()?;
+
+        let mut wav_writer = WavWriter::new(&mut writer, self.spec)?;
+        let mut i16_writer = wav_writer.get_i16_writer(self.samples.len() as _);
         self.samples
             .iter()
             .for_each(|sample| i16_writer.write_sample(*sample));
 
         i16_writer.flush()?;
+        wav_writer.flush()?;
+        wav_writer.finalize()?;
+
+        if let Some(loop_start) = loop_start {
+            let chunk =
+                SampleChunk::with_loop(self.spec.sample_rate, loop_start, frames as u32 - 1);
+
+            let end = writer.seek(SeekFrom::End(0))?;
+            writer.write_all(b"smpl")?;
+            SampleChunk::SIZE.write_le(&mut writer)?;
+            chunk.write(&mut writer)?;
+
+            // update the size of the RIFF chunk
+            let riff_size = (end - start - 8) as u32 + 8 + SampleChunk::SIZE;
+            writer.seek(SeekFrom::Start(start + 4))?;
+            riff_size.write_le(&mut writer)?;
+            writer.seek(SeekFrom::End(0))?;
+        }
+
         writer.flush()?;
-        writer.finalize()?;
 
         Ok(())
     }
@@ -68,3 +252,265 @@ impl Wav {
         self.to_writer(writer)
     }
 }
+
+/// Read all the samples of the wav and convert them to 16-bit, returns the
+/// spec of the converted samples with them.
+///
+/// Samples with less bits get scaled up, samples with more bits (and float ones)
+/// get scaled down and rounded with optional TPDF dither.
+pub(crate) fn read_samples<R: Read>(
+    reader: WavReader<R>,
+    dither: bool,
+) -> Result<(WavSpec, Vec<i16>)> {
+    let spec = reader.spec();
+    let mut dither = dither.then(Tpdf::default);
+    let mut quantize = |sample: f64| {
+        let noise = dither.as_mut().map_or(0.0, Tpdf::next);
+        (sample + noise)
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    };
+
+    let samples = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Int, 16) => reader
+            .into_samples::<i16>()
+            .collect::<std::result::Result<_, _>>()?,
+        (SampleFormat::Int, bits @ 1..=15) => reader
+            .into_samples::<i32>()
+            .map(|s| s.map(|s| (s << (16 - bits)) as i16))
+            .collect::<std::result::Result<_, _>>()?,
+        (SampleFormat::Int, bits @ 17..=32) => {
+            let scale = (1_u64 << (bits - 16)) as f64;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| quantize(s as f64 / scale)))
+                .collect::<std::result::Result<_, _>>()?
+        }
+        (SampleFormat::Float, 32) => reader
+            .into_samples::<f32>()
+            .map(|s| s.map(|s| quantize(s as f64 * 32768.0)))
+            .collect::<std::result::Result<_, _>>()?,
+        (format, bits) => {
+            return Err(Error::InvalidWav(format!(
+                "{bits}-bit {format:?} wavs aren't supported"
+            )))
+        }
+    };
+
+    let spec = WavSpec {
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+        ..spec
+    };
+
+    Ok((spec, samples))
+}
+
+/// Generator of triangular (TPDF) dither noise, with a peak of one 16-bit step.
+///
+/// Uses a simple xorshift so the output is the same for the same input.
+struct Tpdf(u32);
+
+impl Default for Tpdf {
+    fn default() -> Self {
+        Self(0x9E37_79B9)
+    }
+}
+
+impl Tpdf {
+    fn next(&mut self) -> f64 {
+        self.random() - self.random()
+    }
+
+    /// A random number between 0 and 1.
+    fn random(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f64 / u32::MAX as f64
+    }
+}
+
+/// Look for a `smpl` chunk inside the wav and return the start and end (in samples)
+/// of its first loop, the reader get seeked back to where it was.
+///
+/// This doesn't validate the wav, that's up to hound. a malformed `smpl` chunk is
+/// treated the same as a missing one, so the wav still get loaded without a loop.
+pub(crate) fn read_sample_loop<R: Read + Seek>(reader: &mut R) -> Result<Option<(u32, u32)>> {
+    let start = reader.stream_position()?;
+
+    let mut header = [0_u8; 12];
+    let mut sample_loop = None;
+    if reader.read_exact(&mut header).is_ok() && &header[..4] == b"RIFF" {
+        let mut chunk_id = [0_u8; 4];
+        while reader.read_exact(&mut chunk_id).is_ok() {
+            let Ok(len) = u32::read_le(reader) else {
+                break;
+            };
+
+            if &chunk_id == b"smpl" {
+                sample_loop = SampleChunk::read(reader)
+                    .ok()
+                    .and_then(|chunk| chunk.loops.first().map(|l| (l.start, l.end)));
+                break;
+            }
+
+            // chunks are always word aligned
+            reader.seek(SeekFrom::Current(len as i64 + (len & 1) as i64))?;
+        }
+    }
+
+    reader.seek(SeekFrom::Start(start))?;
+
+    Ok(sample_loop)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn loop_round_trip() {
+        let mut wav = Wav {
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: (0..1000).collect(),
+            loop_start: Some(250),
+        };
+
+        let mut buf = Cursor::new(Vec::new());
+        wav.to_writer(&mut buf).unwrap();
+        let read = Wav::new(Cursor::new(buf.get_ref())).expect("failed to read the wav");
+        assert_eq!(read.loop_start(), Some(250));
+        assert_eq!(read.samples(), wav.samples());
+        assert_eq!(
+            u32::from_le_bytes(buf.get_ref()[4..8].try_into().unwrap()) as usize,
+            buf.get_ref().len() - 8
+        );
+
+        // loops that doesn't start inside the audio can't be saved
+        for (samples, loop_start) in [(1000, None), (1000, Some(1000)), (0, Some(0))] {
+            wav.samples = (0..samples).collect();
+            wav.loop_start = loop_start;
+            let mut buf = Cursor::new(Vec::new());
+            wav.to_writer(&mut buf).unwrap();
+            assert_eq!(
+                Wav::new(Cursor::new(buf.get_ref())).unwrap().loop_start(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_sample_chunk() {
+        let wav = Wav {
+            spec: WavSpec {
+                channels: 1,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: (0..1000).collect(),
+            loop_start: Some(250),
+        };
+
+        let mut buf = Cursor::new(Vec::new());
+        wav.to_writer(&mut buf).unwrap();
+        let mut bytes = buf.into_inner();
+
+        // a loop count that doesn't fit inside the chunk
+        let smpl = bytes.windows(4).position(|w| w == b"smpl").unwrap();
+        let num_loops = smpl + 8 + 28;
+        bytes[num_loops..num_loops + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        let read = Wav::new(Cursor::new(&bytes)).expect("failed to read the wav");
+        assert_eq!(read.loop_start(), None);
+        assert_eq!(read.samples(), wav.samples());
+    }
+
+    #[test]
+    fn downmix() {
+        let wav = Wav {
+            spec: WavSpec {
+                channels: 2,
+                sample_rate: 22050,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            },
+            samples: vec![100, -50, 30000, 30000, -32768, -32768],
+            loop_start: Some(1),
+        };
+
+        let mono = wav.downmix(Downmix::Average);
+        assert_eq!(mono.spec().channels, 1);
+        assert_eq!(mono.loop_start(), Some(1));
+        assert_eq!(mono.samples(), [25, 30000, -32768]);
+        assert_eq!(wav.downmix(Downmix::Left).samples(), [100, 30000, -32768]);
+        assert_eq!(wav.downmix(Downmix::Right).samples(), [-50, 30000, -32768]);
+
+        // the sum clip, so it get turned down to fit
+        let mid = wav.downmix(Downmix::Mid);
+        assert_eq!(mid.samples()[2], -i16::MAX);
+        assert!((mid.samples()[1] as i32 - 30000).abs() <= 1);
+
+        assert_eq!(mono.downmix(Downmix::Right).samples(), mono.samples());
+    }
+
+    #[test]
+    fn read_formats() {
+        fn write<S: hound::Sample + Copy>(
+            bits: u16,
+            format: SampleFormat,
+            samples: &[S],
+        ) -> Cursor<Vec<u8>> {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: 22050,
+                bits_per_sample: bits,
+                sample_format: format,
+            };
+            let mut buf = Cursor::new(Vec::new());
+            let mut writer = WavWriter::new(&mut buf, spec).unwrap();
+            samples
+                .iter()
+                .for_each(|&s| writer.write_sample(s).unwrap());
+            writer.finalize().unwrap();
+            buf.set_position(0);
+            buf
+        }
+
+        let expected = [0, 256, -32768, 32767, 1000];
+
+        let wav = Wav::new(write(8, SampleFormat::Int, &[0_i8, 1, -128, 127, 4])).unwrap();
+        assert_eq!(wav.samples(), [0, 256, -32768, 32512, 1024]);
+        assert_eq!(wav.spec().bits_per_sample, 16);
+
+        let samples = expected.map(|s| (s as i32) << 8);
+        let wav = Wav::new(write(24, SampleFormat::Int, &samples)).unwrap();
+        assert_eq!(wav.samples(), expected);
+
+        let samples = expected.map(|s| (s as i32) << 16);
+        let wav = Wav::new(write(32, SampleFormat::Int, &samples)).unwrap();
+        assert_eq!(wav.samples(), expected);
+
+        let samples = [0.0_f32, 0.5, -1.0, 1.5];
+        let wav = Wav::new(write(32, SampleFormat::Float, &samples)).unwrap();
+        assert_eq!(wav.samples(), [0, 16384, -32768, 32767]);
+        assert_eq!(wav.spec().sample_format, SampleFormat::Int);
+
+        // a quarter of a 16-bit step get lost without dither, but survive on average with it
+        let samples = vec![64_i32; 10000];
+        let wav = Wav::new(write(24, SampleFormat::Int, &samples)).unwrap();
+        assert!(wav.samples().iter().all(|&s| s == 0));
+        let wav = Wav::with_dither(write(24, SampleFormat::Int, &samples), true).unwrap();
+        let average = wav.samples().iter().map(|&s| s as f64).sum::<f64>() / 10000.0;
+        assert!((average - 0.25).abs() < 0.05, "average was {average}");
+        assert!(wav.samples().iter().all(|&s| (-1..=1).contains(&s)));
+    }
+}